@@ -0,0 +1,534 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{KookError, KookResult};
+
+// 一条卡片消息最多 5 张卡片, 所有卡片的模块总数不超过 50 个
+pub const MAX_CARDS: usize = 5;
+pub const MAX_MODULES: usize = 50;
+pub const MAX_BUTTONS: usize = 4;
+pub const MAX_IMAGES: usize = 9;
+pub const MAX_CONTEXT_ELEMENTS: usize = 10;
+pub const MAX_HEADER_LEN: usize = 100;
+pub const MAX_PLAIN_TEXT_LEN: usize = 2000;
+pub const MAX_KMARKDOWN_LEN: usize = 5000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Primary,
+    Success,
+    Danger,
+    Warning,
+    Info,
+    Secondary,
+    None,
+    Invisible,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Size {
+    Sm,
+    #[default]
+    Lg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SectionMode {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CountdownMode {
+    Day,
+    Hour,
+    Second,
+}
+
+// 按钮点击后的行为, link 跳转 value 中的链接, return-val 回传 value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Click {
+    Link,
+    ReturnVal,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Element {
+    PlainText {
+        content: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        emoji: bool,
+    },
+    Kmarkdown {
+        content: String,
+    },
+    Image {
+        src: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        alt: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        size: Option<Size>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        circle: bool,
+    },
+    Button {
+        #[serde(default)]
+        theme: Theme,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        value: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        click: Option<Click>,
+        text: Box<Element>,
+    },
+    Paragraph {
+        cols: u8,
+        fields: Vec<Element>,
+    },
+}
+
+impl Element {
+    pub fn plain_text(content: impl Into<String>) -> Self {
+        Element::PlainText {
+            content: content.into(),
+            emoji: false,
+        }
+    }
+
+    pub fn kmarkdown(content: impl Into<String>) -> Self {
+        Element::Kmarkdown { content: content.into() }
+    }
+
+    pub fn image(src: impl Into<String>) -> Self {
+        Element::Image {
+            src: src.into(),
+            alt: String::new(),
+            size: None,
+            circle: false,
+        }
+    }
+
+    // 点击后通过 MessageBtnClick 事件回传 value
+    pub fn button(theme: Theme, text: impl Into<String>, value: impl Into<String>) -> Self {
+        Element::Button {
+            theme,
+            value: value.into(),
+            click: Some(Click::ReturnVal),
+            text: Box::new(Element::plain_text(text)),
+        }
+    }
+
+    pub fn link_button(theme: Theme, text: impl Into<String>, url: impl Into<String>) -> Self {
+        Element::Button {
+            theme,
+            value: url.into(),
+            click: Some(Click::Link),
+            text: Box::new(Element::plain_text(text)),
+        }
+    }
+
+    pub fn paragraph(cols: u8, fields: Vec<Element>) -> Self {
+        Element::Paragraph { cols, fields }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Element::PlainText { .. } => "plain-text",
+            Element::Kmarkdown { .. } => "kmarkdown",
+            Element::Image { .. } => "image",
+            Element::Button { .. } => "button",
+            Element::Paragraph { .. } => "paragraph",
+        }
+    }
+
+    fn is_text(&self) -> bool {
+        matches!(self, Element::PlainText { .. } | Element::Kmarkdown { .. })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Element::PlainText { content, .. } => check_len("plain-text", content, MAX_PLAIN_TEXT_LEN),
+            Element::Kmarkdown { content } => check_len("kmarkdown", content, MAX_KMARKDOWN_LEN),
+            Element::Image { src, .. } => match src.is_empty() {
+                true => Err("image src is empty".to_string()),
+                false => Ok(()),
+            },
+            Element::Button { value, click, text, .. } => {
+                if !text.is_text() {
+                    return Err(format!("button text must be plain-text or kmarkdown, got {}", text.kind()));
+                }
+                if *click == Some(Click::Link) && !(value.starts_with("http://") || value.starts_with("https://")) {
+                    return Err(format!("link button value `{}` is not a http(s) url", value));
+                }
+                text.validate()
+            }
+            Element::Paragraph { cols, fields } => {
+                if !(1..=3).contains(cols) {
+                    return Err(format!("paragraph cols must be 1 to 3, got {}", cols));
+                }
+                if fields.len() > 50 {
+                    return Err(format!("paragraph has {} fields, at most 50", fields.len()));
+                }
+                for field in fields {
+                    if !field.is_text() {
+                        return Err(format!("paragraph field must be plain-text or kmarkdown, got {}", field.kind()));
+                    }
+                    field.validate()?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Module {
+    Header {
+        text: Element,
+    },
+    Section {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mode: Option<SectionMode>,
+        text: Element,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        accessory: Option<Element>,
+    },
+    ImageGroup {
+        elements: Vec<Element>,
+    },
+    Container {
+        elements: Vec<Element>,
+    },
+    ActionGroup {
+        elements: Vec<Element>,
+    },
+    Context {
+        elements: Vec<Element>,
+    },
+    Divider,
+    File {
+        title: String,
+        src: String,
+    },
+    Audio {
+        title: String,
+        src: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cover: Option<String>,
+    },
+    Video {
+        title: String,
+        src: String,
+    },
+    Countdown {
+        mode: CountdownMode,
+        // 毫秒时间戳, second 模式需要 start_time
+        #[serde(rename = "endTime")]
+        end_time: u64,
+        #[serde(rename = "startTime", default, skip_serializing_if = "Option::is_none")]
+        start_time: Option<u64>,
+    },
+    Invite {
+        code: String,
+    },
+}
+
+impl Module {
+    pub fn header(content: impl Into<String>) -> Self {
+        Module::Header {
+            text: Element::plain_text(content),
+        }
+    }
+
+    pub fn section(text: Element) -> Self {
+        Module::Section {
+            mode: None,
+            text,
+            accessory: None,
+        }
+    }
+
+    pub fn section_with(text: Element, mode: SectionMode, accessory: Element) -> Self {
+        Module::Section {
+            mode: Some(mode),
+            text,
+            accessory: Some(accessory),
+        }
+    }
+
+    pub fn image_group(images: Vec<Element>) -> Self {
+        Module::ImageGroup { elements: images }
+    }
+
+    pub fn container(images: Vec<Element>) -> Self {
+        Module::Container { elements: images }
+    }
+
+    pub fn action_group(buttons: Vec<Element>) -> Self {
+        Module::ActionGroup { elements: buttons }
+    }
+
+    pub fn context(elements: Vec<Element>) -> Self {
+        Module::Context { elements }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Module::Header { .. } => "header",
+            Module::Section { .. } => "section",
+            Module::ImageGroup { .. } => "image-group",
+            Module::Container { .. } => "container",
+            Module::ActionGroup { .. } => "action-group",
+            Module::Context { .. } => "context",
+            Module::Divider => "divider",
+            Module::File { .. } => "file",
+            Module::Audio { .. } => "audio",
+            Module::Video { .. } => "video",
+            Module::Countdown { .. } => "countdown",
+            Module::Invite { .. } => "invite",
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Module::Header { text } => match text {
+                Element::PlainText { content, .. } => check_len("header", content, MAX_HEADER_LEN),
+                other => Err(format!("header text must be plain-text, got {}", other.kind())),
+            },
+            Module::Section { text, accessory, .. } => {
+                if !matches!(text, Element::PlainText { .. } | Element::Kmarkdown { .. } | Element::Paragraph { .. }) {
+                    return Err(format!("section text must be plain-text, kmarkdown or paragraph, got {}", text.kind()));
+                }
+                text.validate()?;
+                match accessory {
+                    Some(accessory @ (Element::Image { .. } | Element::Button { .. })) => accessory.validate(),
+                    Some(other) => Err(format!("section accessory must be image or button, got {}", other.kind())),
+                    None => Ok(()),
+                }
+            }
+            Module::ImageGroup { elements } | Module::Container { elements } => {
+                check_count(self.kind(), "images", elements.len(), 1, MAX_IMAGES)?;
+                check_elements(self.kind(), elements, |x| matches!(x, Element::Image { .. }))
+            }
+            Module::ActionGroup { elements } => {
+                check_count(self.kind(), "buttons", elements.len(), 1, MAX_BUTTONS)?;
+                check_elements(self.kind(), elements, |x| matches!(x, Element::Button { .. }))
+            }
+            Module::Context { elements } => {
+                check_count(self.kind(), "elements", elements.len(), 1, MAX_CONTEXT_ELEMENTS)?;
+                check_elements(self.kind(), elements, |x| x.is_text() || matches!(x, Element::Image { .. }))
+            }
+            Module::Divider => Ok(()),
+            Module::File { src, .. } | Module::Audio { src, .. } | Module::Video { src, .. } => match src.is_empty() {
+                true => Err(format!("{} src is empty", self.kind())),
+                false => Ok(()),
+            },
+            Module::Countdown { mode, end_time, start_time } => match (mode, start_time) {
+                (CountdownMode::Second, None) => Err("countdown in second mode needs start_time".to_string()),
+                (_, Some(start_time)) if start_time >= end_time => Err("countdown start_time must be before end_time".to_string()),
+                _ => Ok(()),
+            },
+            Module::Invite { code } => match code.is_empty() {
+                true => Err("invite code is empty".to_string()),
+                false => Ok(()),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "card")]
+pub struct Card {
+    #[serde(default)]
+    pub theme: Theme,
+    #[serde(default)]
+    pub size: Size,
+    // 卡片左侧边框颜色, 如 #aaaaaa
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    pub modules: Vec<Module>,
+}
+
+impl Card {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    pub fn with_size(mut self, size: Size) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_color(mut self, color: impl Into<String>) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+
+    pub fn module(mut self, module: Module) -> Self {
+        self.modules.push(module);
+        self
+    }
+}
+
+// 消息内容为卡片数组的 json
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CardMessage(pub Vec<Card>);
+
+impl CardMessage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn card(mut self, card: Card) -> Self {
+        self.0.push(card);
+        self
+    }
+
+    pub fn validate(&self) -> KookResult<()> {
+        if self.0.is_empty() {
+            return Err(KookError::Card("card message has no cards".to_string()));
+        }
+        if self.0.len() > MAX_CARDS {
+            return Err(KookError::Card(format!("card message has {} cards, at most {}", self.0.len(), MAX_CARDS)));
+        }
+        let modules: usize = self.0.iter().map(|x| x.modules.len()).sum();
+        if modules > MAX_MODULES {
+            return Err(KookError::Card(format!("card message has {} modules, at most {}", modules, MAX_MODULES)));
+        }
+        for (i, card) in self.0.iter().enumerate() {
+            for (j, module) in card.modules.iter().enumerate() {
+                module
+                    .validate()
+                    .map_err(|err| KookError::Card(format!("card {} module {} ({}): {}", i, j, module.kind(), err)))?;
+            }
+        }
+        Ok(())
+    }
+
+    // 校验后序列化为消息内容
+    pub fn to_content(&self) -> KookResult<String> {
+        self.validate()?;
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+fn check_len(kind: &str, content: &str, max: usize) -> Result<(), String> {
+    let len = content.chars().count();
+    match len > max {
+        true => Err(format!("{} is {} chars, at most {}", kind, len, max)),
+        false => Ok(()),
+    }
+}
+
+fn check_count(kind: &str, what: &str, count: usize, min: usize, max: usize) -> Result<(), String> {
+    match (min..=max).contains(&count) {
+        true => Ok(()),
+        false => Err(format!("{} has {} {}, expected {} to {}", kind, count, what, min, max)),
+    }
+}
+
+fn check_elements(kind: &str, elements: &[Element], allowed: impl Fn(&Element) -> bool) -> Result<(), String> {
+    for element in elements {
+        if !allowed(element) {
+            return Err(format!("{} does not accept {}", kind, element.kind()));
+        }
+        element.validate()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_to_kook_json() {
+        let message = CardMessage::new().card(
+            Card::new()
+                .with_theme(Theme::Warning)
+                .with_size(Size::Sm)
+                .module(Module::header("标题"))
+                .module(Module::section_with(
+                    Element::kmarkdown("**hi**"),
+                    SectionMode::Right,
+                    Element::button(Theme::Primary, "点我", "clicked"),
+                ))
+                .module(Module::Divider)
+                .module(Module::Countdown {
+                    mode: CountdownMode::Hour,
+                    end_time: 1000,
+                    start_time: None,
+                }),
+        );
+        let expected = serde_json::json!([{
+            "type": "card",
+            "theme": "warning",
+            "size": "sm",
+            "modules": [
+                {"type": "header", "text": {"type": "plain-text", "content": "标题"}},
+                {
+                    "type": "section",
+                    "mode": "right",
+                    "text": {"type": "kmarkdown", "content": "**hi**"},
+                    "accessory": {
+                        "type": "button",
+                        "theme": "primary",
+                        "value": "clicked",
+                        "click": "return-val",
+                        "text": {"type": "plain-text", "content": "点我"}
+                    }
+                },
+                {"type": "divider"},
+                {"type": "countdown", "mode": "hour", "endTime": 1000}
+            ]
+        }]);
+        let content = message.to_content().unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&content).unwrap(), expected);
+        assert_eq!(serde_json::from_value::<CardMessage>(expected).unwrap(), message);
+    }
+
+    #[test]
+    fn rejects_too_many_cards_and_modules() {
+        let card = Card::new().module(Module::Divider);
+        let message = CardMessage(vec![card; MAX_CARDS + 1]);
+        assert!(matches!(message.validate(), Err(KookError::Card(err)) if err.contains("6 cards")));
+
+        let card = Card::new();
+        let card = (0..MAX_MODULES + 1).fold(card, |card, _| card.module(Module::Divider));
+        let message = CardMessage::new().card(card);
+        assert!(matches!(message.validate(), Err(KookError::Card(err)) if err.contains("51 modules")));
+    }
+
+    #[test]
+    fn rejects_invalid_modules() {
+        let buttons = (0..5).map(|i| Element::button(Theme::Primary, "b", i.to_string())).collect();
+        let message = CardMessage::new().card(Card::new().module(Module::Divider).module(Module::action_group(buttons)));
+        let err = message.validate().unwrap_err().to_string();
+        assert!(err.contains("card 0 module 1 (action-group)"), "{}", err);
+        assert!(err.contains("5 buttons"), "{}", err);
+
+        let message = CardMessage::new().card(Card::new().module(Module::image_group(vec![Element::plain_text("x")])));
+        assert!(message.validate().is_err());
+
+        let message = CardMessage::new().card(Card::new().module(Module::action_group(vec![Element::link_button(
+            Theme::Info,
+            "b",
+            "not a url",
+        )])));
+        assert!(message.validate().is_err());
+
+        assert!(CardMessage::new().validate().is_err());
+    }
+}
@@ -4,7 +4,7 @@ use super::bool_as_u8;
 use super::objects::{Channel, NotifyType, Role};
 use serde_json::Value;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum Event {
     Text(TextEvent),
@@ -18,6 +18,7 @@ pub enum Event {
 }
 
 impl Event {
+    #[allow(clippy::wrong_self_convention)]
     pub(crate) fn to_arc(self) -> Arc<Self> {
         Arc::new(self)
     }
@@ -34,9 +35,79 @@ impl Event {
             Event::System(e) => e.author_id.as_str(),
         }
     }
+
+    pub fn target_id(&self) -> &str {
+        match self {
+            Event::Text(e) => e.target_id.as_str(),
+            Event::Image(e) => e.target_id.as_str(),
+            Event::Video(e) => e.target_id.as_str(),
+            Event::File(e) => e.target_id.as_str(),
+            Event::KMarkdown(e) => e.target_id.as_str(),
+            Event::Card(e) => e.target_id.as_str(),
+            Event::Item(e) => e.target_id.as_str(),
+            Event::System(e) => e.target_id.as_str(),
+        }
+    }
+
+    pub fn msg_id(&self) -> &str {
+        match self {
+            Event::Text(e) => e.msg_id.as_str(),
+            Event::Image(e) => e.msg_id.as_str(),
+            Event::Video(e) => e.msg_id.as_str(),
+            Event::File(e) => e.msg_id.as_str(),
+            Event::KMarkdown(e) => e.msg_id.as_str(),
+            Event::Card(e) => e.msg_id.as_str(),
+            Event::Item(e) => e.msg_id.as_str(),
+            Event::System(e) => e.msg_id.as_str(),
+        }
+    }
+
+    // 私聊消息与私聊中的系统事件没有服务器
+    pub fn guild_id(&self) -> Option<&str> {
+        let guild_id = match self {
+            Event::Text(e) => e.extra.guild_id.as_str(),
+            Event::Image(e) => e.extra.guild_id.as_str(),
+            Event::Video(e) => e.extra.guild_id.as_str(),
+            Event::File(e) => e.extra.guild_id.as_str(),
+            Event::KMarkdown(e) => e.extra.guild_id.as_str(),
+            Event::Card(e) => e.extra.guild_id.as_str(),
+            Event::Item(_) => "",
+            Event::System(e) if e.channel_type == "GROUP" => e.target_id.as_str(),
+            Event::System(_) => "",
+        };
+        (!guild_id.is_empty()).then_some(guild_id)
+    }
+
+    pub fn author_is_bot(&self) -> bool {
+        match self {
+            Event::Text(e) => e.extra.author.bot,
+            Event::Image(e) => e.extra.author.bot,
+            Event::Video(e) => e.extra.author.bot,
+            Event::File(e) => e.extra.author.bot,
+            Event::KMarkdown(e) => e.extra.author.bot,
+            Event::Card(e) => e.extra.author.bot,
+            Event::Item(e) => e.extra.author.bot,
+            Event::System(_) => false,
+        }
+    }
+
+    // 私聊消息的 channel_type 为 PERSON
+    pub fn is_direct(&self) -> bool {
+        let channel_type = match self {
+            Event::Text(e) => e.channel_type.as_str(),
+            Event::Image(e) => e.channel_type.as_str(),
+            Event::Video(e) => e.channel_type.as_str(),
+            Event::File(e) => e.channel_type.as_str(),
+            Event::KMarkdown(e) => e.channel_type.as_str(),
+            Event::Card(e) => e.channel_type.as_str(),
+            Event::Item(_) => return false,
+            Event::System(e) => e.channel_type.as_str(),
+        };
+        channel_type == "PERSON"
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EventType<const V: u8>;
 
 #[derive(Debug, thiserror::Error)]
@@ -66,7 +137,7 @@ impl<'de, const V: u8> Deserialize<'de> for EventType<V> {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SystemEvent {
     #[serde(rename = "channel_type")]
     pub channel_type: String,
@@ -96,7 +167,7 @@ pub struct SystemEvent {
     pub nonce: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type", content = "body")]
 pub enum SystemExtra {
     #[serde(rename = "added_reaction")]
@@ -252,7 +323,7 @@ pub enum SystemExtra {
     },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TextEvent {
     #[serde(rename = "channel_type")]
     pub channel_type: String,
@@ -282,7 +353,7 @@ pub struct TextEvent {
     pub nonce: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TextExtra {
     #[serde(rename = "guild_id")]
     pub guild_id: String,
@@ -309,7 +380,7 @@ pub struct TextExtra {
     pub author: Author,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ImageEvent {
     #[serde(rename = "channel_type")]
     pub channel_type: String,
@@ -339,7 +410,7 @@ pub struct ImageEvent {
     pub nonce: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ImageExtra {
     #[serde(rename = "code")]
     pub code: String,
@@ -354,7 +425,7 @@ pub struct ImageExtra {
     pub author: Author,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ImageAttachments {
     #[serde(rename = "type")]
     pub attachments_type: String,
@@ -366,7 +437,7 @@ pub struct ImageAttachments {
     pub url: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VideoEvent {
     #[serde(rename = "channel_type")]
     pub channel_type: String,
@@ -396,7 +467,7 @@ pub struct VideoEvent {
     pub nonce: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VideoExtra {
     #[serde(rename = "guild_id")]
     pub guild_id: String,
@@ -411,7 +482,7 @@ pub struct VideoExtra {
     pub author: Author,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VideoAttachments {
     #[serde(rename = "type")]
     pub attachments_type: String,
@@ -438,7 +509,7 @@ pub struct VideoAttachments {
     pub height: i64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileEvent {
     #[serde(rename = "channel_type")]
     pub channel_type: String,
@@ -468,7 +539,7 @@ pub struct FileEvent {
     pub nonce: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileExtra {
     #[serde(rename = "guild_id")]
     pub guild_id: String,
@@ -483,7 +554,7 @@ pub struct FileExtra {
     pub author: Author,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileAttachments {
     #[serde(rename = "type")]
     pub attachments_type: String,
@@ -501,7 +572,7 @@ pub struct FileAttachments {
     pub size: i64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct KMarkdownEvent {
     #[serde(rename = "channel_type")]
     pub channel_type: String,
@@ -531,7 +602,7 @@ pub struct KMarkdownEvent {
     pub nonce: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct KMarkdownExtra {
     #[serde(rename = "guild_id")]
     pub guild_id: String,
@@ -564,7 +635,7 @@ pub struct KMarkdownExtra {
     pub kmarkdown: Kmarkdown,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AuthorInfo {
     #[serde(rename = "id")]
     pub id: String,
@@ -592,8 +663,11 @@ pub struct AuthorInfo {
 
     #[serde(rename = "roles")]
     pub roles: Vec<u64>,
+
+    #[serde(rename = "bot", default)]
+    pub bot: bool,
 }
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Kmarkdown {
     #[serde(rename = "raw_content")]
     pub raw_content: String,
@@ -606,7 +680,7 @@ pub struct Kmarkdown {
 }
 
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MentionPart {
     #[serde(rename = "id")]
     pub id: String,
@@ -621,7 +695,7 @@ pub struct MentionPart {
     pub avatar: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CardEvent {
     #[serde(rename = "channel_type")]
     pub channel_type: String,
@@ -651,7 +725,7 @@ pub struct CardEvent {
     pub nonce: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CardExtra {
     #[serde(rename = "guild_id")]
     pub guild_id: String,
@@ -681,7 +755,7 @@ pub struct CardExtra {
     pub author: AuthorInfo,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ItemEvent {
     #[serde(rename = "channel_type")]
     pub channel_type: ItemContent,
@@ -711,7 +785,7 @@ pub struct ItemEvent {
     pub nonce: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ItemContent {
     #[serde(rename = "type")]
     pub item_content_type: String,
@@ -720,7 +794,7 @@ pub struct ItemContent {
     pub data: ItemData,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ItemData {
     #[serde(rename = "user_id")]
     pub user_id: String,
@@ -732,7 +806,7 @@ pub struct ItemData {
     pub item_id: i64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ItemExtra {
     #[serde(rename = "mention")]
     pub mention: Vec<String>,
@@ -744,7 +818,7 @@ pub struct ItemExtra {
     pub kmarkdown: ItemKmarkdown,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ItemKmarkdown {
     #[serde(rename = "mention")]
     pub mention: Vec<String>,
@@ -756,7 +830,7 @@ pub struct ItemKmarkdown {
     pub item_part: Vec<Value>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Emoji {
     #[serde(rename = "id")]
     pub id: String,
@@ -765,7 +839,7 @@ pub struct Emoji {
     pub name: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Author {
     #[serde(rename = "identify_num")]
     pub identify_num: String,
@@ -784,4 +858,7 @@ pub struct Author {
 
     #[serde(rename = "roles")]
     pub roles: Vec<u64>,
+
+    #[serde(rename = "bot", default)]
+    pub bot: bool,
 }
@@ -1,64 +1,135 @@
-use crate::{api::response::Page, error::KookResult, url::http_api};
-use reqwest::header::AUTHORIZATION;
+use crate::{
+    api::{
+        pagination::{PageQuery, Paginator},
+        ratelimit,
+        response::Page,
+        retry::RetryPolicy,
+    },
+    error::{KookError, KookResult},
+    url::http_api,
+};
+use reqwest::{
+    header::AUTHORIZATION,
+    multipart::{Form, Part},
+    Body, RequestBuilder, StatusCode,
+};
+use futures_util::TryStreamExt;
 use serde::{de::DeserializeOwned, Serialize};
 
 use super::{
-    objects::Guild,
+    card::CardMessage,
+    event::{Event, SystemEvent, SystemExtra},
+    objects::{Channel, Guild, MessageType, Role, User},
+    permission::Permissions,
     request,
     response::{self, ResponseWrap},
 };
 
 impl crate::Bot {
-    async fn http_get<T: DeserializeOwned>(&self, url: &str, query: &[(&str, &str)]) -> KookResult<T> {
-        let ret = self
-            .http_client
-            .get(url)
-            .header(AUTHORIZATION, self.token_str.as_str())
-            .query(query)
-            .send()
-            .await?
-            .text()
-            .await?;
-        tracing::debug!("{}", ret);
-        let ret: ResponseWrap<T> = serde_json::from_str(&ret)?;
-        ret.into_result()
+    pub(crate) fn api_url(&self, path: &str) -> String {
+        format!("{}/api/v{}/{}", self.base_url, self.api_version, path)
     }
 
-    async fn http_get_page_all<T: DeserializeOwned>(&self, url: &str, query: &[(&str, &str)]) -> KookResult<Vec<T>> {
-        let mut ret = Vec::new();
-        for i in 0..Page::<T>::MAX_PAGE_SIZE {
-            let resp = self
-                .http_client
-                .get(url)
-                .header(AUTHORIZATION, self.token_str.as_str())
-                .query(query)
-                .query(&[("page", i.to_string().as_str()), ("page_size", "50"), ("sort", "0")])
-                .send()
-                .await?
-                .text()
-                .await?;
-            tracing::debug!("{}", resp);
-            let resp: ResponseWrap<Page<T>> = serde_json::from_str(&resp)?;
-            let resp = resp.into_result()?;
-            ret.extend(resp.items);
-            if resp.meta.page == resp.meta.page_total {
-                break;
+    // 发送请求并处理限速, 被限速时等待重置后重试, 请求体不能复制 (如 multipart) 时不会重发
+    // idempotent 为 true 时网络错误和 5xx 按 RetryPolicy 重试
+    async fn http_send(&self, path: &str, idempotent: bool, req: RequestBuilder) -> KookResult<String> {
+        let route = path.to_string();
+        let mut retries = 0;
+        let mut attempts = 0;
+        let mut req = req;
+        loop {
+            self.rate_limiter.acquire(&route).await;
+            let next = req.try_clone();
+            let ret = match req.header(AUTHORIZATION, self.token_str.as_str()).send().await {
+                Ok(resp) => {
+                    let status = resp.status();
+                    let headers = resp.headers().clone();
+                    let ret = match status.is_server_error() {
+                        true => Err(resp.error_for_status().expect_err("server error status")),
+                        false => resp.text().await,
+                    };
+                    let limited = status == StatusCode::TOO_MANY_REQUESTS || ret.as_deref().is_ok_and(ratelimit::limited_body);
+                    if let Some(retry_after) = self.rate_limiter.update(&route, limited, &headers) {
+                        match next {
+                            Some(next) if retries < self.rate_limiter.max_retries => {
+                                retries += 1;
+                                tracing::warn!("rate limited route:{} retry after:{:?} retries:{}", route, retry_after, retries);
+                                req = next;
+                                continue;
+                            }
+                            // 不能重发时直接返回, 不等待重置
+                            _ => return Err(KookError::RateLimited { route, retry_after }),
+                        }
+                    }
+                    ret
+                }
+                Err(err) => Err(err),
+            };
+            match (ret, next) {
+                (Ok(ret), _) => {
+                    tracing::debug!("{}", ret);
+                    return Ok(ret);
+                }
+                (Err(err), Some(next)) if idempotent && attempts < self.retry.max_retries && RetryPolicy::transient(&err) => {
+                    attempts += 1;
+                    let delay = self.retry.delay(attempts);
+                    tracing::warn!("request route:{} failed: {}, retry after:{:?} attempts:{}", route, err, delay, attempts);
+                    tokio::time::sleep(delay).await;
+                    req = next;
+                }
+                (Err(err), _) => return Err(err.into()),
             }
         }
-        Ok(ret)
     }
 
-    async fn http_post<T: DeserializeOwned>(&self, url: &str, req: &impl Serialize) -> KookResult<T> {
-        let ret = self
-            .http_client
-            .post(url)
-            .header(AUTHORIZATION, self.token_str.as_str())
-            .json(req)
-            .send()
-            .await?
-            .text()
-            .await?;
-        tracing::debug!("{}", ret);
+    async fn http_get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, &str)]) -> KookResult<T> {
+        let url = self.api_url(path);
+        let ret = self.http_send(path, true, self.http_client.get(&url).query(query)).await?;
+        let ret: ResponseWrap<T> = serde_json::from_str(&ret)?;
+        ret.into_result()
+    }
+
+    pub(crate) async fn http_get_page<T: DeserializeOwned>(&self, path: &str, query: Vec<(String, String)>) -> KookResult<Page<T>> {
+        let url = self.api_url(path);
+        let ret = self.http_send(path, true, self.http_client.get(&url).query(&query)).await?;
+        let ret: ResponseWrap<Page<T>> = serde_json::from_str(&ret)?;
+        ret.into_result()
+    }
+
+    pub(crate) fn http_get_stream<T: DeserializeOwned + Send + 'static>(
+        &self, path: &'static str, query: &[(&str, &str)], page: PageQuery,
+    ) -> Paginator<'_, T> {
+        let query = query.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        Paginator::new(self, path, query, page)
+    }
+
+    pub(crate) async fn http_get_page_all<T: DeserializeOwned + Send + 'static>(
+        &self, path: &'static str, query: &[(&str, &str)],
+    ) -> KookResult<Vec<T>> {
+        self.http_get_stream(path, query, PageQuery::default()).try_collect().await
+    }
+
+    async fn http_post<T: DeserializeOwned>(&self, path: &str, req: &impl Serialize) -> KookResult<T> {
+        self.http_post_with(path, false, req).await
+    }
+
+    // 重复发送不会产生副作用的 POST, 失败时可以重试
+    // 删除和退出类接口重复发送会返回不存在的错误, 不能使用
+    async fn http_post_idempotent<T: DeserializeOwned>(&self, path: &str, req: &impl Serialize) -> KookResult<T> {
+        self.http_post_with(path, true, req).await
+    }
+
+    async fn http_post_with<T: DeserializeOwned>(&self, path: &str, idempotent: bool, req: &impl Serialize) -> KookResult<T> {
+        let url = self.api_url(path);
+        let ret = self.http_send(path, idempotent, self.http_client.post(&url).json(req)).await?;
+        let ret: ResponseWrap<T> = serde_json::from_str(&ret)?;
+        ret.into_result()
+    }
+
+    // multipart 表单只能发送一次, 被限速时直接返回错误
+    async fn http_post_multipart<T: DeserializeOwned>(&self, path: &str, form: Form) -> KookResult<T> {
+        let url = self.api_url(path);
+        let ret = self.http_send(path, false, self.http_client.post(&url).multipart(form)).await?;
         let ret: ResponseWrap<T> = serde_json::from_str(&ret)?;
         ret.into_result()
     }
@@ -70,6 +141,10 @@ impl crate::Bot {
         self.http_get_page_all(http_api::GUILD_LIST, &[]).await
     }
 
+    pub fn guild_list_stream(&self, page: PageQuery) -> Paginator<'_, response::GuildListItem> {
+        self.http_get_stream(http_api::GUILD_LIST, &[], page)
+    }
+
     pub async fn guild_view(&self, guild_id: &str) -> KookResult<Guild> {
         self.http_get(http_api::GUILD_VIEW, &[("guild_id", guild_id)]).await
     }
@@ -78,9 +153,18 @@ impl crate::Bot {
         self.http_get_page_all(http_api::GUILD_USER_LIST, &[("guild_id", guild_id)]).await
     }
 
+    pub fn guild_user_list_stream(&self, guild_id: &str, page: PageQuery) -> Paginator<'_, response::GuildUserListItem> {
+        self.http_get_stream(http_api::GUILD_USER_LIST, &[("guild_id", guild_id)], page)
+    }
+
+    // 按条件筛选服务器成员, 例如角色、搜索、按加入时间排序
+    pub fn guild_user_query(&self, query: &request::GuildUserQuery) -> Paginator<'_, response::GuildUserListItem> {
+        Paginator::new(self, http_api::GUILD_USER_LIST, query.to_query(), query.page.clone())
+    }
+
     pub async fn guild_nickname(&self, guild_id: &str, user_id: &str, nickname: &str) -> KookResult<()> {
         let _: response::Empty = self
-            .http_post(http_api::GUILD_NICKNAME, &request::GuildNickname { guild_id, user_id, nickname })
+            .http_post_idempotent(http_api::GUILD_NICKNAME, &request::GuildNickname { guild_id, user_id, nickname })
             .await?;
         Ok(())
     }
@@ -91,6 +175,116 @@ impl crate::Bot {
     }
 }
 
+// 服务器角色权限接口
+impl crate::Bot {
+    pub async fn guild_role_list(&self, guild_id: &str) -> KookResult<Vec<Role>> {
+        self.http_get_page_all(http_api::GUILD_ROLE_LIST, &[("guild_id", guild_id)]).await
+    }
+
+    pub fn guild_role_list_stream(&self, guild_id: &str, page: PageQuery) -> Paginator<'_, Role> {
+        self.http_get_stream(http_api::GUILD_ROLE_LIST, &[("guild_id", guild_id)], page)
+    }
+
+    pub async fn guild_role_create(&self, guild_id: &str, name: impl Into<Option<&str>>) -> KookResult<Role> {
+        let req = request::GuildRoleCreate { guild_id, name: name.into() };
+        self.http_post(http_api::GUILD_ROLE_CREATE, &req).await
+    }
+
+    pub async fn guild_role_update(&self, req: &request::GuildRoleUpdate<'_>) -> KookResult<Role> {
+        self.http_post_idempotent(http_api::GUILD_ROLE_UPDATE, req).await
+    }
+
+    pub async fn guild_role_delete(&self, guild_id: &str, role_id: u64) -> KookResult<()> {
+        let _: response::Empty = self
+            .http_post(http_api::GUILD_ROLE_DELETE, &request::GuildRoleDelete { guild_id, role_id })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn guild_role_grant(&self, guild_id: &str, user_id: &str, role_id: u64) -> KookResult<response::GuildRoleGrant> {
+        self.http_post_idempotent(http_api::GUILD_ROLE_GRANT, &request::GuildRoleGrant { guild_id, user_id, role_id })
+            .await
+    }
+
+    pub async fn guild_role_revoke(&self, guild_id: &str, user_id: &str, role_id: u64) -> KookResult<response::GuildRoleGrant> {
+        self.http_post_idempotent(http_api::GUILD_ROLE_REVOKE, &request::GuildRoleGrant { guild_id, user_id, role_id })
+            .await
+    }
+}
+
+// 频道相关接口
+impl crate::Bot {
+    pub async fn channel_list(&self, guild_id: &str) -> KookResult<Vec<response::ChannelListItem>> {
+        self.http_get_page_all(http_api::CHANNEL_LIST, &[("guild_id", guild_id)]).await
+    }
+
+    pub fn channel_list_stream(&self, guild_id: &str, page: PageQuery) -> Paginator<'_, response::ChannelListItem> {
+        self.http_get_stream(http_api::CHANNEL_LIST, &[("guild_id", guild_id)], page)
+    }
+
+    pub async fn channel_view(&self, target_id: &str) -> KookResult<Channel> {
+        self.http_get(http_api::CHANNEL_VIEW, &[("target_id", target_id)]).await
+    }
+
+    pub async fn channel_create(&self, req: &request::ChannelCreate<'_>) -> KookResult<Channel> {
+        self.http_post(http_api::CHANNEL_CREATE, req).await
+    }
+
+    pub async fn channel_update(&self, req: &request::ChannelUpdate<'_>) -> KookResult<Channel> {
+        self.http_post_idempotent(http_api::CHANNEL_UPDATE, req).await
+    }
+
+    pub async fn channel_delete(&self, channel_id: &str) -> KookResult<()> {
+        let _: response::Empty = self.http_post(http_api::CHANNEL_DELETE, &request::ChannelDelete { channel_id }).await?;
+        Ok(())
+    }
+
+    // 语音频道中的用户
+    pub async fn channel_user_list(&self, channel_id: &str) -> KookResult<Vec<User>> {
+        self.http_get(http_api::CHANNEL_USER_LIST, &[("channel_id", channel_id)]).await
+    }
+
+    pub async fn channel_move_user(&self, target_id: &str, user_ids: &[&str]) -> KookResult<()> {
+        let _: response::Empty = self
+            .http_post_idempotent(http_api::CHANNEL_MOVE_USER, &request::ChannelMoveUser { target_id, user_ids })
+            .await?;
+        Ok(())
+    }
+}
+
+// 频道角色权限接口
+impl crate::Bot {
+    pub async fn channel_role_index(&self, channel_id: &str) -> KookResult<response::ChannelRoleIndex> {
+        self.http_get(http_api::CHANNEL_ROLE_INDEX, &[("channel_id", channel_id)]).await
+    }
+
+    pub async fn channel_role_create(&self, channel_id: &str, target: request::ChannelRoleTarget<'_>) -> KookResult<response::ChannelRole> {
+        self.http_post(http_api::CHANNEL_ROLE_CREATE, &request::ChannelRole::new(channel_id, target)).await
+    }
+
+    pub async fn channel_role_update(
+        &self,
+        channel_id: &str,
+        target: request::ChannelRoleTarget<'_>,
+        allow: Permissions,
+        deny: Permissions,
+    ) -> KookResult<response::ChannelRole> {
+        let req = request::ChannelRole {
+            allow: Some(allow),
+            deny: Some(deny),
+            ..request::ChannelRole::new(channel_id, target)
+        };
+        self.http_post_idempotent(http_api::CHANNEL_ROLE_UPDATE, &req).await
+    }
+
+    pub async fn channel_role_delete(&self, channel_id: &str, target: request::ChannelRoleTarget<'_>) -> KookResult<()> {
+        let _: response::Empty = self
+            .http_post(http_api::CHANNEL_ROLE_DELETE, &request::ChannelRole::new(channel_id, target))
+            .await?;
+        Ok(())
+    }
+}
+
 // 获取网关连接地址
 impl crate::Bot {
     pub async fn gateway_index(&self, compress: bool) -> KookResult<String> {
@@ -115,19 +309,661 @@ impl crate::Bot {
     }
 
     pub async fn user_offline(&self) -> KookResult<()> {
-        let _: [u8; 0] = self.http_post(http_api::USER_OFFLINE, &()).await?;
+        let _: [u8; 0] = self.http_post_idempotent(http_api::USER_OFFLINE, &()).await?;
         Ok(())
     }
 }
 
 // 频道消息接口
 impl crate::Bot {
+    pub async fn message_list(&self, req: &request::MessageList<'_>) -> KookResult<Vec<response::ChannelMessage>> {
+        let page_size = req.page_size.map(|x| x.to_string());
+        let mut query = vec![("target_id", req.target_id)];
+        if let Some(msg_id) = req.msg_id {
+            query.push(("msg_id", msg_id));
+        }
+        if req.pin {
+            query.push(("pin", "1"));
+        }
+        if let Some(flag) = req.flag {
+            query.push(("flag", flag.as_str()));
+        }
+        if let Some(ref page_size) = page_size {
+            query.push(("page_size", page_size));
+        }
+        let ret: response::MessageList = self.http_get(http_api::MESSAGE_LIST, &query).await?;
+        Ok(ret.items)
+    }
+
+    pub async fn message_view(&self, msg_id: &str) -> KookResult<response::ChannelMessage> {
+        self.http_get(http_api::MESSAGE_VIEW, &[("msg_id", msg_id)]).await
+    }
+
     pub async fn message_create(&self, target_id: &str, content: &str) -> KookResult<response::MessageCreate> {
-        let ret = self.http_post(http_api::MESSAGE_CREATE, &request::MessageCreate{
-            message_create_type: 1,
-            target_id,
-            content
-        }).await?;
-        Ok(ret)
+        self.message_send(&request::MessageCreate::new(target_id, content)).await
+    }
+
+    // 带 nonce 时服务端会去重, 失败可以重试
+    pub async fn message_send(&self, req: &request::MessageCreate<'_>) -> KookResult<response::MessageCreate> {
+        self.http_post_with(http_api::MESSAGE_CREATE, req.nonce.is_some(), req).await
+    }
+
+    pub async fn message_update(&self, req: &request::MessageUpdate<'_>) -> KookResult<()> {
+        let _: response::Empty = self.http_post_idempotent(http_api::MESSAGE_UPDATE, req).await?;
+        Ok(())
+    }
+
+    pub async fn message_delete(&self, msg_id: &str) -> KookResult<()> {
+        let _: response::Empty = self.http_post(http_api::MESSAGE_DELETE, &request::MessageDelete { msg_id }).await?;
+        Ok(())
+    }
+
+    pub async fn message_reaction_list(&self, msg_id: &str, emoji: &str) -> KookResult<Vec<response::ReactionUser>> {
+        self.http_get(http_api::MESSAGE_REACTION_LIST, &[("msg_id", msg_id), ("emoji", emoji)]).await
+    }
+
+    pub async fn message_add_reaction(&self, msg_id: &str, emoji: &str) -> KookResult<()> {
+        let _: response::Empty = self
+            .http_post_idempotent(http_api::MESSAGE_ADD_REACTION, &request::MessageReaction { msg_id, emoji })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn message_delete_reaction(&self, msg_id: &str, emoji: &str, user_id: impl Into<Option<&str>>) -> KookResult<()> {
+        let _: response::Empty = self
+            .http_post(
+                http_api::MESSAGE_DELETE_REACTION,
+                &request::MessageDeleteReaction {
+                    msg_id,
+                    emoji,
+                    user_id: user_id.into(),
+                },
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+// 私信聊天会话接口
+impl crate::Bot {
+    pub async fn user_chat_list(&self) -> KookResult<Vec<response::UserChat>> {
+        self.http_get_page_all(http_api::USER_CHAT_LIST, &[]).await
+    }
+
+    pub fn user_chat_list_stream(&self, page: PageQuery) -> Paginator<'_, response::UserChat> {
+        self.http_get_stream(http_api::USER_CHAT_LIST, &[], page)
+    }
+
+    pub async fn user_chat_view(&self, chat_code: &str) -> KookResult<response::UserChat> {
+        self.http_get(http_api::USER_CHAT_VIEW, &[("chat_code", chat_code)]).await
+    }
+
+    pub async fn user_chat_create(&self, target_id: &str) -> KookResult<response::UserChat> {
+        self.http_post_idempotent(http_api::USER_CHAT_CREATE, &request::UserChatCreate { target_id }).await
+    }
+
+    pub async fn user_chat_delete(&self, chat_code: &str) -> KookResult<()> {
+        let _: response::Empty = self.http_post(http_api::USER_CHAT_DELETE, &request::UserChatDelete { chat_code }).await?;
+        Ok(())
+    }
+}
+
+// 用户私聊消息接口
+impl crate::Bot {
+    pub async fn direct_message_list(&self, req: &request::DirectMessageList<'_>) -> KookResult<Vec<response::DirectMessage>> {
+        let page_size = req.page_size.map(|x| x.to_string());
+        let mut query = vec![req.target.as_query()];
+        if let Some(msg_id) = req.msg_id {
+            query.push(("msg_id", msg_id));
+        }
+        if let Some(flag) = req.flag {
+            query.push(("flag", flag.as_str()));
+        }
+        if let Some(ref page_size) = page_size {
+            query.push(("page_size", page_size));
+        }
+        let ret: response::DirectMessageList = self.http_get(http_api::DIRECT_MESSAGE_LIST, &query).await?;
+        Ok(ret.items)
+    }
+
+    pub async fn direct_message_view(&self, target: request::DirectTarget<'_>, msg_id: &str) -> KookResult<response::DirectMessage> {
+        self.http_get(http_api::DIRECT_MESSAGE_VIEW, &[target.as_query(), ("msg_id", msg_id)]).await
+    }
+
+    pub async fn direct_message_create(&self, req: &request::DirectMessageCreate<'_>) -> KookResult<response::MessageCreate> {
+        self.http_post_with(http_api::DIRECT_MESSAGE_CREATE, req.nonce.is_some(), req).await
+    }
+
+    pub async fn direct_message_update(&self, req: &request::DirectMessageUpdate<'_>) -> KookResult<()> {
+        let _: response::Empty = self.http_post_idempotent(http_api::DIRECT_MESSAGE_UPDATE, req).await?;
+        Ok(())
+    }
+
+    pub async fn direct_message_delete(&self, msg_id: &str) -> KookResult<()> {
+        let _: response::Empty = self.http_post(http_api::DIRECT_MESSAGE_DELETE, &request::MessageDelete { msg_id }).await?;
+        Ok(())
+    }
+
+    pub async fn direct_message_reaction_list(&self, msg_id: &str, emoji: &str) -> KookResult<Vec<response::ReactionUser>> {
+        self.http_get(http_api::DIRECT_MESSAGE_REACTION_LIST, &[("msg_id", msg_id), ("emoji", emoji)]).await
+    }
+
+    pub async fn direct_message_add_reaction(&self, msg_id: &str, emoji: &str) -> KookResult<()> {
+        let _: response::Empty = self
+            .http_post_idempotent(http_api::DIRECT_MESSAGE_ADD_REACTION, &request::MessageReaction { msg_id, emoji })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn direct_message_delete_reaction(&self, msg_id: &str, emoji: &str) -> KookResult<()> {
+        let _: response::Empty = self
+            .http_post(http_api::DIRECT_MESSAGE_DELETE_REACTION, &request::MessageReaction { msg_id, emoji })
+            .await?;
+        Ok(())
+    }
+}
+
+// 回复消息, 私聊消息通过私信回复
+// 按钮点击回复给点击的用户与所在频道, 其他系统事件和道具事件无法回复
+impl crate::Bot {
+    pub async fn reply(&self, event: &Event, content: &str) -> KookResult<response::MessageCreate> {
+        let (direct, target_id, msg_id) = match event {
+            Event::System(SystemEvent {
+                channel_type,
+                extra: SystemExtra::MessageBtnClick { msg_id, user_id, target_id, .. },
+                ..
+            }) => {
+                if channel_type == "PERSON" {
+                    (true, user_id.as_str(), msg_id.as_str())
+                } else {
+                    (false, target_id.as_str(), msg_id.as_str())
+                }
+            }
+            Event::System(_) | Event::Item(_) => return Err(KookError::Custom("event cannot be replied to".to_string())),
+            _ if event.is_direct() => (true, event.author_id(), event.msg_id()),
+            _ => (false, event.target_id(), event.msg_id()),
+        };
+        if direct {
+            let req = request::DirectMessageCreate {
+                quote: Some(msg_id),
+                ..request::DirectMessageCreate::new(request::DirectTarget::TargetId(target_id), content)
+            };
+            self.direct_message_create(&req).await
+        } else {
+            let req = request::MessageCreate {
+                quote: Some(msg_id),
+                ..request::MessageCreate::new(target_id, content)
+            };
+            self.message_send(&req).await
+        }
+    }
+}
+
+// 媒体文件上传
+impl crate::Bot {
+    pub async fn asset_create(&self, asset: request::Asset) -> KookResult<String> {
+        let mime = asset.mime().to_string();
+        let part = match asset.source {
+            request::AssetSource::Path(path) => {
+                let file = tokio::fs::File::open(&path).await?;
+                let len = file.metadata().await?.len();
+                Part::stream_with_length(Body::wrap_stream(tokio_util::io::ReaderStream::new(file)), len)
+            }
+            request::AssetSource::Bytes(bytes) => Part::bytes(bytes),
+            request::AssetSource::Reader(reader) => Part::stream(Body::wrap_stream(tokio_util::io::ReaderStream::new(reader))),
+        };
+        let part = part.file_name(asset.file_name).mime_str(&mime)?;
+        let ret: response::AssetCreate = self.http_post_multipart(http_api::ASSET_CREATE, Form::new().part("file", part)).await?;
+        Ok(ret.url)
+    }
+
+    pub async fn send_image(&self, target_id: &str, asset: request::Asset) -> KookResult<response::MessageCreate> {
+        self.send_asset(target_id, asset, MessageType::Image).await
+    }
+
+    pub async fn send_file(&self, target_id: &str, asset: request::Asset) -> KookResult<response::MessageCreate> {
+        self.send_asset(target_id, asset, MessageType::File).await
+    }
+
+    pub async fn send_video(&self, target_id: &str, asset: request::Asset) -> KookResult<response::MessageCreate> {
+        self.send_asset(target_id, asset, MessageType::Video).await
+    }
+
+    async fn send_asset(&self, target_id: &str, asset: request::Asset, message_type: MessageType) -> KookResult<response::MessageCreate> {
+        let url = self.asset_create(asset).await?;
+        let req = request::MessageCreate {
+            message_type,
+            ..request::MessageCreate::new(target_id, &url)
+        };
+        self.message_send(&req).await
+    }
+}
+
+// 卡片消息
+impl crate::Bot {
+    pub async fn send_card(&self, target_id: &str, card: &CardMessage) -> KookResult<response::MessageCreate> {
+        let content = card.to_content()?;
+        let req = request::MessageCreate {
+            message_type: MessageType::Card,
+            ..request::MessageCreate::new(target_id, &content)
+        };
+        self.message_send(&req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    };
+
+    use hyper::{Response, StatusCode};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{api::testing::mock_server, Bot, Token};
+
+    fn mock_bot(url: &str) -> Bot {
+        Bot::builder(Token::Bot("test".to_string())).with_base_url(url).build()
+    }
+
+    const OK: &str = r#"{"code":0,"message":"","data":{}}"#;
+    const LIMITED: &str = r#"{"code":40000,"message":"请求过于频繁","data":{}}"#;
+
+    fn limited(reset: &str) -> Response<hyper::Body> {
+        Response::builder()
+            .status(StatusCode::TOO_MANY_REQUESTS)
+            .header("X-Rate-Limit-Limit", "5")
+            .header("X-Rate-Limit-Remaining", "0")
+            .header("X-Rate-Limit-Reset", reset)
+            .header("X-Rate-Limit-Bucket", "message/create")
+            .body(LIMITED.into())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn resolves_against_base_url() {
+        let url = mock_server(|req| {
+            assert_eq!(req.uri().path(), "/proxy/api/v4/gateway/index");
+            assert_eq!(req.headers()[AUTHORIZATION], "Bot test");
+            assert_eq!(req.headers()[reqwest::header::USER_AGENT], "kook-test");
+            Response::new(r#"{"code":0,"message":"","data":{"url":"wss://gateway"}}"#.into())
+        });
+        let client = reqwest::Client::builder().user_agent("kook-test").build().unwrap();
+        let bot = Bot::builder(Token::Bot("test".to_string()))
+            .with_base_url(format!("{url}/proxy/"))
+            .with_api_version(4)
+            .with_client(client)
+            .build();
+        assert_eq!(bot.gateway_index(false).await.unwrap(), "wss://gateway");
+    }
+
+    fn flaky_server(failures: u32) -> (String, Arc<AtomicU32>) {
+        let count = Arc::new(AtomicU32::new(0));
+        let counter = count.clone();
+        let url = mock_server(move |_| match counter.fetch_add(1, Ordering::SeqCst) < failures {
+            true => Response::builder().status(StatusCode::BAD_GATEWAY).body(hyper::Body::empty()).unwrap(),
+            false => Response::new(r#"{"code":0,"message":"","data":{"msg_id":"m1","msg_timestamp":0,"nonce":""}}"#.into()),
+        });
+        (url, count)
+    }
+
+    fn retry_bot(url: &str) -> Bot {
+        Bot::builder(Token::Bot("test".to_string()))
+            .with_base_url(url)
+            .with_retry_policy(RetryPolicy {
+                initial_delay: Duration::from_millis(10),
+                ..Default::default()
+            })
+            .build()
+    }
+
+    #[tokio::test]
+    async fn retries_get_on_server_error() {
+        let (url, count) = flaky_server(2);
+        let bot = retry_bot(&url);
+        bot.http_get::<Value>(http_api::MESSAGE_VIEW, &[]).await.unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_retry_policy() {
+        let (url, count) = flaky_server(u32::MAX);
+        let bot = retry_bot(&url);
+        let err = bot.http_get::<Value>(http_api::MESSAGE_VIEW, &[]).await.unwrap_err();
+        assert!(matches!(err, KookError::Http(ref err) if err.status() == Some(reqwest::StatusCode::BAD_GATEWAY)));
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn retries_connection_error() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let bot = retry_bot(&url);
+        let start = Instant::now();
+        let err = bot.http_get::<Value>(http_api::MESSAGE_VIEW, &[]).await.unwrap_err();
+        assert!(matches!(err, KookError::Http(ref err) if err.is_connect()));
+        // 重试 3 次, 等待 10ms + 20ms + 40ms
+        assert!(start.elapsed().as_millis() >= 50);
+    }
+
+    #[tokio::test]
+    async fn message_create_retries_only_with_nonce() {
+        let (url, count) = flaky_server(1);
+        let bot = retry_bot(&url);
+        assert!(bot.message_create("channel", "hello").await.is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        let (url, count) = flaky_server(1);
+        let bot = retry_bot(&url);
+        let req = request::MessageCreate {
+            nonce: Some("n1"),
+            ..request::MessageCreate::new("channel", "hello")
+        };
+        assert_eq!(bot.message_send(&req).await.unwrap().msg_id, "m1");
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn deletes_are_not_retried() {
+        for delete in 0..3 {
+            let (url, count) = flaky_server(1);
+            let bot = retry_bot(&url);
+            let ret = match delete {
+                0 => bot.message_delete("m1").await,
+                1 => bot.direct_message_delete("m1").await,
+                _ => bot.guild_leave("g1").await.map(|_| ()),
+            };
+            assert!(ret.is_err());
+            assert_eq!(count.load(Ordering::SeqCst), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn guild_user_query_stops_early() {
+        let queries = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = queries.clone();
+        let url = mock_server(move |req| {
+            let query = req.uri().query().unwrap_or_default().to_string();
+            let page: i64 = query.split('&').find_map(|x| x.strip_prefix("page=")).unwrap().parse().unwrap();
+            recorder.lock().unwrap().push(query);
+            // 每页两个用户, 按加入时间倒序
+            let items: Vec<Value> = (0..2)
+                .map(|i| {
+                    let n = (page - 1) * 2 + i;
+                    serde_json::json!({
+                        "id": n.to_string(), "username": "u", "identify_num": "0001", "online": false, "status": 0, "bot": false,
+                        "avatar": "", "vip_avatar": "", "nickname": "u", "roles": [7], "joined_at": 1000 - n * 100
+                    })
+                })
+                .collect();
+            let body = serde_json::json!({
+                "code": 0,
+                "message": "",
+                "data": {"items": items, "meta": {"page": page, "page_total": 10, "page_size": 2, "total": 20}, "sort": {}}
+            });
+            Response::new(body.to_string().into())
+        });
+        let bot = mock_bot(&url);
+        let query = request::GuildUserQuery::new("guild")
+            .with_role_id(7)
+            .sort_by_joined_at(request::SortOrder::Desc)
+            .with_page(PageQuery::default().with_page_size(2));
+        let users: Vec<response::GuildUserListItem> = bot
+            .guild_user_query(&query)
+            .try_take_while(|user| std::future::ready(Ok(user.joined_at > 750)))
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(users.iter().map(|x| x.id.as_str()).collect::<Vec<_>>(), vec!["0", "1", "2"]);
+        assert_eq!(
+            *queries.lock().unwrap(),
+            vec![
+                "guild_id=guild&role_id=7&joined_at=1&page=1&page_size=2",
+                "guild_id=guild&role_id=7&joined_at=1&page=2&page_size=2"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_after_429() {
+        let count = Arc::new(AtomicU32::new(0));
+        let counter = count.clone();
+        let url = mock_server(move |_| match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => limited("0.1"),
+            _ => Response::new(OK.into()),
+        });
+        let bot = mock_bot(&url);
+        let start = Instant::now();
+        bot.http_post::<Value>(http_api::MESSAGE_CREATE, &()).await.unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        assert!(start.elapsed().as_millis() >= 90);
+    }
+
+    #[tokio::test]
+    async fn retries_limited_body_with_ok_status() {
+        let count = Arc::new(AtomicU32::new(0));
+        let counter = count.clone();
+        let url = mock_server(move |_| match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => Response::builder().header("X-Rate-Limit-Reset", "0.1").body(LIMITED.into()).unwrap(),
+            _ => Response::new(OK.into()),
+        });
+        let bot = mock_bot(&url);
+        let start = Instant::now();
+        bot.http_post::<Value>(http_api::MESSAGE_CREATE, &()).await.unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        assert!(start.elapsed().as_millis() >= 90);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn waits_for_empty_bucket() {
+        let url = mock_server(|_| {
+            Response::builder()
+                .header("X-Rate-Limit-Limit", "1")
+                .header("X-Rate-Limit-Remaining", "0")
+                .header("X-Rate-Limit-Reset", "0.3")
+                .header("X-Rate-Limit-Bucket", "guild/view")
+                .body(OK.into())
+                .unwrap()
+        });
+        let bot = mock_bot(&url);
+        bot.http_get::<Value>(http_api::GUILD_VIEW, &[]).await.unwrap();
+        let start = Instant::now();
+        bot.http_get::<Value>(http_api::GUILD_VIEW, &[]).await.unwrap();
+        assert!(start.elapsed().as_millis() >= 250);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let count = Arc::new(AtomicU32::new(0));
+        let counter = count.clone();
+        let url = mock_server(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            limited("0")
+        });
+        let bot = Bot::builder(Token::Bot("test".to_string()))
+            .with_base_url(url)
+            .with_rate_limit_retries(2)
+            .build();
+        let err = bot.http_get::<Value>(http_api::MESSAGE_LIST, &[]).await.unwrap_err();
+        assert!(matches!(err, KookError::RateLimited { ref route, .. } if route == http_api::MESSAGE_LIST));
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn multipart_is_not_resent() {
+        let count = Arc::new(AtomicU32::new(0));
+        let counter = count.clone();
+        let url = mock_server(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            limited("0.2")
+        });
+        let bot = mock_bot(&url);
+        let form = Form::new().text("file", "data");
+        let start = Instant::now();
+        let err = bot.http_post_multipart::<Value>(http_api::ASSET_CREATE, form).await.unwrap_err();
+        assert!(start.elapsed() < Duration::from_millis(150));
+        assert!(matches!(err, KookError::RateLimited { retry_after, .. } if retry_after == Duration::from_millis(200)));
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    type Sent<T> = Arc<std::sync::Mutex<Vec<(String, T)>>>;
+
+    // 记录每次请求的路径与 json 请求体
+    fn reply_server() -> (String, Sent<Value>) {
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let record = sent.clone();
+        let url = mock_server(move |req| {
+            let body = serde_json::from_slice(req.body()).unwrap();
+            record.lock().unwrap().push((req.uri().path().to_string(), body));
+            Response::new(r#"{"code":0,"message":"","data":{"msg_id":"reply","msg_timestamp":0,"nonce":""}}"#.into())
+        });
+        (url, sent)
+    }
+
+    fn text_event(channel_type: &str) -> Event {
+        let mut body = crate::api::testing::text_event_body("msg");
+        body["channel_type"] = channel_type.into();
+        serde_json::from_value(body).unwrap()
+    }
+
+    fn click_event(channel_type: &str) -> Event {
+        serde_json::from_value(serde_json::json!({
+            "channel_type": channel_type,
+            "type": 255,
+            "target_id": "bot",
+            "author_id": "1",
+            "content": "[系统消息]",
+            "extra": {
+                "type": "message_btn_click",
+                "body": {"value": "ok", "msg_id": "card", "user_id": "user", "target_id": "channel"}
+            },
+            "msg_id": "system",
+            "msg_timestamp": 0,
+            "nonce": ""
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn reply_in_channel() {
+        let (url, sent) = reply_server();
+        mock_bot(&url).reply(&text_event("GROUP"), "hi").await.unwrap();
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent[0].0, "/api/v3/message/create");
+        assert_eq!(sent[0].1["target_id"], "channel");
+        assert_eq!(sent[0].1["quote"], "msg");
+    }
+
+    #[tokio::test]
+    async fn reply_in_direct_message() {
+        let (url, sent) = reply_server();
+        mock_bot(&url).reply(&text_event("PERSON"), "hi").await.unwrap();
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent[0].0, "/api/v3/direct-message/create");
+        assert_eq!(sent[0].1["target_id"], "user");
+        assert_eq!(sent[0].1["quote"], "msg");
+    }
+
+    #[tokio::test]
+    async fn reply_to_button_click() {
+        let (url, sent) = reply_server();
+        let bot = mock_bot(&url);
+        bot.reply(&click_event("PERSON"), "hi").await.unwrap();
+        bot.reply(&click_event("GROUP"), "hi").await.unwrap();
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent[0].0, "/api/v3/direct-message/create");
+        assert_eq!(sent[0].1["target_id"], "user");
+        assert_eq!(sent[0].1["quote"], "card");
+        assert_eq!(sent[1].0, "/api/v3/message/create");
+        assert_eq!(sent[1].1["target_id"], "channel");
+        assert_eq!(sent[1].1["quote"], "card");
+    }
+
+    #[tokio::test]
+    async fn reply_rejects_other_system_events() {
+        let (url, sent) = reply_server();
+        let event: Event = serde_json::from_value(serde_json::json!({
+            "channel_type": "GROUP",
+            "type": 255,
+            "target_id": "guild",
+            "author_id": "1",
+            "content": "[系统消息]",
+            "extra": {"type": "self_exited_guild", "body": {"guild_id": "guild"}},
+            "msg_id": "system",
+            "msg_timestamp": 0,
+            "nonce": ""
+        }))
+        .unwrap();
+        let err = mock_bot(&url).reply(&event, "hi").await.unwrap_err();
+        assert!(matches!(err, KookError::Custom(_)));
+        assert!(sent.lock().unwrap().is_empty());
+    }
+
+    // asset/create 记录 multipart 请求体, message/create 记录 json 请求体
+    fn asset_server() -> (String, Sent<String>) {
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let record = sent.clone();
+        let url = mock_server(move |req| {
+            let path = req.uri().path().to_string();
+            let content_type = req.headers()[reqwest::header::CONTENT_TYPE].to_str().unwrap().to_string();
+            let body = String::from_utf8_lossy(req.body()).into_owned();
+            let data = if path.ends_with("asset/create") {
+                assert!(content_type.starts_with("multipart/form-data; boundary="));
+                r#"{"url":"https://img.kookapp.cn/asset"}"#
+            } else {
+                r#"{"msg_id":"reply","msg_timestamp":0,"nonce":""}"#
+            };
+            record.lock().unwrap().push((path, body));
+            Response::new(format!(r#"{{"code":0,"message":"","data":{}}}"#, data).into())
+        });
+        (url, sent)
+    }
+
+    #[tokio::test]
+    async fn send_image_streams_reader() {
+        let (url, sent) = asset_server();
+        let reader = std::io::Cursor::new(b"png data".to_vec());
+        mock_bot(&url).send_image("channel", request::Asset::reader("cat.png", reader)).await.unwrap();
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent[0].0, "/api/v3/asset/create");
+        assert!(sent[0].1.contains(r#"Content-Disposition: form-data; name="file"; filename="cat.png""#));
+        assert!(sent[0].1.contains("Content-Type: image/png\r\n\r\npng data\r\n"));
+        assert_eq!(sent[1].0, "/api/v3/message/create");
+        let message: Value = serde_json::from_str(&sent[1].1).unwrap();
+        assert_eq!(message["type"], 2);
+        assert_eq!(message["target_id"], "channel");
+        assert_eq!(message["content"], "https://img.kookapp.cn/asset");
+    }
+
+    #[tokio::test]
+    async fn send_file_streams_path() {
+        let path = std::env::temp_dir().join(format!("kook_rs_{}.txt", std::process::id()));
+        std::fs::write(&path, "file data").unwrap();
+        let (url, sent) = asset_server();
+        let ret = mock_bot(&url).send_file("channel", request::Asset::path(&path)).await;
+        std::fs::remove_file(&path).unwrap();
+        ret.unwrap();
+        let sent = sent.lock().unwrap();
+        let file_name = path.file_name().unwrap().to_str().unwrap();
+        assert!(sent[0].1.contains(&format!(r#"name="file"; filename="{}""#, file_name)));
+        assert!(sent[0].1.contains("Content-Type: text/plain\r\n\r\nfile data\r\n"));
+        let message: Value = serde_json::from_str(&sent[1].1).unwrap();
+        assert_eq!(message["type"], 4);
+    }
+
+    #[tokio::test]
+    async fn asset_create_uses_explicit_content_type() {
+        let (url, sent) = asset_server();
+        let asset = request::Asset::bytes("blob", b"raw".to_vec()).with_content_type("image/webp");
+        assert_eq!(mock_bot(&url).asset_create(asset).await.unwrap(), "https://img.kookapp.cn/asset");
+        let sent = sent.lock().unwrap();
+        assert!(sent[0].1.contains(r#"name="file"; filename="blob""#));
+        assert!(sent[0].1.contains("Content-Type: image/webp\r\n\r\nraw\r\n"));
+    }
+}
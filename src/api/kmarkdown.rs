@@ -0,0 +1,562 @@
+use std::{collections::HashMap, fmt::Display};
+
+// 需要转义的特殊字符
+const SPECIAL_CHARS: &[char] = &['\\', '*', '~', '[', ']', '(', ')', '>', '-', '`'];
+
+pub fn escape(text: &str) -> String {
+    let mut ret = String::with_capacity(text.len());
+    for c in text.chars() {
+        if SPECIAL_CHARS.contains(&c) {
+            ret.push('\\');
+        }
+        ret.push(c);
+    }
+    ret
+}
+
+// 构造 KMarkdown 文本, 普通文本会自动转义
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KMarkdown {
+    buf: String,
+}
+
+impl KMarkdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(mut self, text: &str) -> Self {
+        self.buf.push_str(&escape(text));
+        self
+    }
+
+    // 原样拼接, 不做转义
+    pub fn raw(mut self, raw: &str) -> Self {
+        self.buf.push_str(raw);
+        self
+    }
+
+    pub fn bold(self, text: &str) -> Self {
+        self.wrap("**", text, "**")
+    }
+
+    pub fn italic(self, text: &str) -> Self {
+        self.wrap("*", text, "*")
+    }
+
+    pub fn strikethrough(self, text: &str) -> Self {
+        self.wrap("~~", text, "~~")
+    }
+
+    pub fn underline(self, text: &str) -> Self {
+        self.wrap("(ins)", text, "(ins)")
+    }
+
+    pub fn spoiler(self, text: &str) -> Self {
+        self.wrap("(spl)", text, "(spl)")
+    }
+
+    pub fn link(mut self, text: &str, url: &str) -> Self {
+        self.buf.push('[');
+        self.buf.push_str(&escape(text));
+        self.buf.push_str("](");
+        self.buf.push_str(&escape(url));
+        self.buf.push(')');
+        self
+    }
+
+    // 代码中不解析其他语法, 只需避免提前结束
+    pub fn code(mut self, code: &str) -> Self {
+        self.buf.push('`');
+        self.buf.push_str(&code.replace('`', "\\`"));
+        self.buf.push('`');
+        self
+    }
+
+    pub fn code_block(mut self, lang: &str, code: &str) -> Self {
+        self.buf.push_str("```");
+        self.buf.push_str(lang);
+        self.buf.push('\n');
+        self.buf.push_str(&code.replace("```", "\\`\\`\\`"));
+        if !code.ends_with('\n') {
+            self.buf.push('\n');
+        }
+        self.buf.push_str("```\n");
+        self
+    }
+
+    // 引用以两个换行结束
+    pub fn quote(mut self, text: &str) -> Self {
+        self.start_line();
+        self.buf.push_str("> ");
+        self.buf.push_str(&escape(text));
+        self.buf.push_str("\n\n");
+        self
+    }
+
+    pub fn divider(mut self) -> Self {
+        self.start_line();
+        self.buf.push_str("---\n");
+        self
+    }
+
+    pub fn newline(mut self) -> Self {
+        self.buf.push('\n');
+        self
+    }
+
+    pub fn mention_user(self, user_id: &str) -> Self {
+        self.wrap("(met)", user_id, "(met)")
+    }
+
+    pub fn mention_all(self) -> Self {
+        self.raw("(met)all(met)")
+    }
+
+    pub fn mention_here(self) -> Self {
+        self.raw("(met)here(met)")
+    }
+
+    pub fn mention_role(self, role_id: &str) -> Self {
+        self.wrap("(rol)", role_id, "(rol)")
+    }
+
+    pub fn channel(self, channel_id: &str) -> Self {
+        self.wrap("(chn)", channel_id, "(chn)")
+    }
+
+    pub fn emoji(mut self, name: &str, id: &str) -> Self {
+        self.buf.push_str("(emj)");
+        self.buf.push_str(&escape(name));
+        self.buf.push_str("(emj)[");
+        self.buf.push_str(&escape(id));
+        self.buf.push(']');
+        self
+    }
+
+    pub fn build(self) -> String {
+        self.buf
+    }
+
+    fn wrap(mut self, start: &str, text: &str, end: &str) -> Self {
+        self.buf.push_str(start);
+        self.buf.push_str(&escape(text));
+        self.buf.push_str(end);
+        self
+    }
+
+    fn start_line(&mut self) {
+        if !self.buf.is_empty() && !self.buf.ends_with('\n') {
+            self.buf.push('\n');
+        }
+    }
+}
+
+impl Display for KMarkdown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.buf)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Text(String),
+    Bold(Vec<Node>),
+    Italic(Vec<Node>),
+    Strikethrough(Vec<Node>),
+    Underline(Vec<Node>),
+    Spoiler(Vec<Node>),
+    Link { text: String, url: String },
+    Code(String),
+    CodeBlock { lang: String, code: String },
+    Quote(Vec<Node>),
+    Divider,
+    MentionUser(String),
+    MentionAll,
+    MentionHere,
+    MentionRole(String),
+    Channel(String),
+    Emoji { name: String, id: String },
+}
+
+pub fn parse(text: &str) -> Vec<Node> {
+    let mut parser = Parser {
+        src: text,
+        pos: 0,
+        ends: Vec::new(),
+        closers: HashMap::new(),
+    };
+    parser.nodes().0
+}
+
+// 去掉所有标记后的纯文本, 提及转为 @id, 频道转为 #id
+pub fn plain_text(text: &str) -> String {
+    to_plain_text(&parse(text))
+}
+
+pub fn to_plain_text(nodes: &[Node]) -> String {
+    let mut ret = String::new();
+    write_plain_text(nodes, &mut ret);
+    ret
+}
+
+fn write_plain_text(nodes: &[Node], ret: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) | Node::Code(text) => ret.push_str(text),
+            Node::CodeBlock { code, .. } => ret.push_str(code),
+            Node::Bold(nodes) | Node::Italic(nodes) | Node::Strikethrough(nodes) | Node::Underline(nodes) | Node::Spoiler(nodes) => {
+                write_plain_text(nodes, ret)
+            }
+            Node::Quote(nodes) => {
+                write_plain_text(nodes, ret);
+                ret.push('\n');
+            }
+            Node::Link { text, .. } => ret.push_str(text),
+            Node::Divider => {}
+            Node::MentionUser(id) | Node::MentionRole(id) => {
+                ret.push('@');
+                ret.push_str(id);
+            }
+            Node::MentionAll => ret.push_str("@all"),
+            Node::MentionHere => ret.push_str("@here"),
+            Node::Channel(id) => {
+                ret.push('#');
+                ret.push_str(id);
+            }
+            Node::Emoji { name, .. } => {
+                ret.push(':');
+                ret.push_str(name);
+                ret.push(':');
+            }
+        }
+    }
+}
+
+// 嵌套层数上限, 超出后的标记按普通文本处理
+const MAX_DEPTH: usize = 32;
+
+type Wrap = fn(Vec<Node>) -> Node;
+
+// 单遍解析, 未闭合的标记直接当作普通文本, 不回溯重新扫描
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    // 外层还未闭合的结束标记, 最内层在最后
+    ends: Vec<&'static str>,
+    // 每种结束标记上一次的查找结果 (查找起点, 找到的位置)
+    closers: HashMap<&'static str, (usize, Option<usize>)>,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.pos == 0 || self.src[..self.pos].ends_with('\n')
+    }
+
+    // 返回解析的节点, 以及是否遇到了结束标记
+    // 遇到更外层的结束标记时停下, 交给外层处理
+    fn nodes(&mut self) -> (Vec<Node>, bool) {
+        let mut nodes = Vec::new();
+        while !self.rest().is_empty() {
+            let rest = self.rest();
+            if let Some((&end, outer)) = self.ends.split_last() {
+                if rest.starts_with(end) {
+                    self.pos += end.len();
+                    return (nodes, true);
+                }
+                if outer.iter().any(|end| rest.starts_with(end)) {
+                    return (nodes, false);
+                }
+            }
+            if let Some((start, end, wrap)) = self.opener() {
+                self.nested(start, end, wrap, &mut nodes);
+                continue;
+            }
+            if let Some(node) = self.special() {
+                nodes.push(node);
+                continue;
+            }
+            let mut chars = rest.chars();
+            let c = match chars.next() {
+                Some('\\') => match chars.next() {
+                    Some(c) => {
+                        self.pos += 1;
+                        c
+                    }
+                    None => '\\',
+                },
+                Some(c) => c,
+                None => break,
+            };
+            self.pos += c.len_utf8();
+            match nodes.last_mut() {
+                Some(Node::Text(text)) => text.push(c),
+                _ => nodes.push(Node::Text(c.to_string())),
+            }
+        }
+        (nodes, false)
+    }
+
+    // 可以嵌套其他标记的语法, 返回开始标记, 结束标记和节点构造
+    fn opener(&self) -> Option<(&'static str, &'static str, Wrap)> {
+        let rest = self.rest();
+        if self.ends.len() >= MAX_DEPTH {
+            return None;
+        }
+        if self.at_line_start() && rest.starts_with("> ") {
+            return Some(("> ", "\n\n", Node::Quote));
+        }
+        let openers: [(&'static str, &'static str, Wrap); 6] = [
+            ("(ins)", "(ins)", Node::Underline),
+            ("(spl)", "(spl)", Node::Spoiler),
+            ("***", "***", |nodes| Node::Bold(vec![Node::Italic(nodes)])),
+            ("**", "**", Node::Bold),
+            ("*", "*", Node::Italic),
+            ("~~", "~~", Node::Strikethrough),
+        ];
+        openers.into_iter().find(|(start, ..)| rest.starts_with(start))
+    }
+
+    // 没有结束标记时开始标记按普通文本处理, 已经解析的内容保留
+    // 引用在空行, 外层结束或文本结束时结束
+    fn nested(&mut self, start: &'static str, end: &'static str, wrap: Wrap, nodes: &mut Vec<Node>) {
+        self.pos += start.len();
+        self.ends.push(end);
+        let (inner, closed) = self.nodes();
+        self.ends.pop();
+        if closed || end == "\n\n" {
+            nodes.push(wrap(inner));
+            return;
+        }
+        for node in std::iter::once(Node::Text(start.to_string())).chain(inner) {
+            match (nodes.last_mut(), node) {
+                (Some(Node::Text(text)), Node::Text(more)) => text.push_str(&more),
+                (_, node) => nodes.push(node),
+            }
+        }
+    }
+
+    fn special(&mut self) -> Option<Node> {
+        let start = self.pos;
+        let rest = self.rest();
+        if rest.starts_with('\\') {
+            return None;
+        }
+        if self.at_line_start() && rest.starts_with("---") && matches!(rest[3..].chars().next(), None | Some('\n')) {
+            self.pos += if rest.len() > 3 { 4 } else { 3 };
+            return Some(Node::Divider);
+        }
+        if rest.starts_with("```") {
+            let end = self.find_closer(start + 3, "```", false)?;
+            let body = &rest[3..3 + end];
+            let (lang, code) = body.split_once('\n').unwrap_or(("", body));
+            let node = Node::CodeBlock {
+                lang: lang.trim().to_string(),
+                code: code.replace("\\`", "`"),
+            };
+            self.pos += 3 + end + 3;
+            return Some(node);
+        }
+        if rest.starts_with('`') {
+            let end = self.find_closer(start + 1, "`", true)?;
+            let node = Node::Code(rest[1..1 + end].replace("\\`", "`"));
+            self.pos += end + 2;
+            return Some(node);
+        }
+        for (tag, kind) in [("(met)", 0), ("(rol)", 1), ("(chn)", 2)] {
+            if rest.starts_with(tag) {
+                let end = self.find_closer(start + tag.len(), tag, false)?;
+                let id = unescape(&rest[tag.len()..tag.len() + end]);
+                self.pos += tag.len() * 2 + end;
+                return Some(match (kind, id.as_str()) {
+                    (0, "all") => Node::MentionAll,
+                    (0, "here") => Node::MentionHere,
+                    (0, _) => Node::MentionUser(id),
+                    (1, _) => Node::MentionRole(id),
+                    _ => Node::Channel(id),
+                });
+            }
+        }
+        if rest.starts_with("(emj)") {
+            let end = self.find_closer(start + 5, "(emj)", false)?;
+            if !rest[5 + end + 5..].starts_with('[') {
+                return None;
+            }
+            let id_end = self.find_closer(start + 5 + end + 6, "]", true)?;
+            let node = Node::Emoji {
+                name: unescape(&rest[5..5 + end]),
+                id: unescape(&rest[5 + end + 6..5 + end + 6 + id_end]),
+            };
+            self.pos += 5 + end + 5 + 1 + id_end + 1;
+            return Some(node);
+        }
+        if rest.starts_with('[') {
+            let text_end = self.find_closer(start + 1, "](", true)?;
+            let url_end = self.find_closer(start + 1 + text_end + 2, ")", true)?;
+            let url = &rest[1 + text_end + 2..];
+            let node = Node::Link {
+                text: plain_text(&rest[1..1 + text_end]),
+                url: unescape(&url[..url_end]),
+            };
+            self.pos += 1 + text_end + 2 + url_end + 1;
+            return Some(node);
+        }
+        None
+    }
+
+    // 从 from 开始查找结束标记, 返回相对 from 的偏移
+    // 标记前一个字符不是转义符, 上一次的结果在找到的位置之前都可以复用
+    // is_none_or 需要 Rust 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    fn find_closer(&mut self, from: usize, pat: &'static str, escaped: bool) -> Option<usize> {
+        if let Some(&(start, found)) = self.closers.get(pat) {
+            if from >= start && found.map_or(true, |found| from <= found) {
+                return found.map(|found| found - from);
+            }
+        }
+        let text = &self.src[from..];
+        let found = if escaped { find_unescaped(text, pat) } else { text.find(pat) };
+        self.closers.insert(pat, (from, found.map(|i| from + i)));
+        found
+    }
+}
+
+fn find_unescaped(text: &str, pat: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if text[i..].starts_with(pat) {
+            return Some(i);
+        }
+    }
+    None
+}
+
+fn unescape(text: &str) -> String {
+    let mut ret = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => ret.push(chars.next().unwrap_or('\\')),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_escapes_user_text() {
+        let text = KMarkdown::new()
+            .mention_user("123")
+            .text(" said ")
+            .bold("**not bold** (met)all(met)")
+            .link("a [b]", "https://example.com/(x)")
+            .build();
+        assert_eq!(
+            text,
+            r"(met)123(met) said **\*\*not bold\*\* \(met\)all\(met\)**[a \[b\]](https://example.com/\(x\))"
+        );
+        assert_eq!(
+            parse(&text),
+            vec![
+                Node::MentionUser("123".to_string()),
+                Node::Text(" said ".to_string()),
+                Node::Bold(vec![Node::Text("**not bold** (met)all(met)".to_string())]),
+                Node::Link {
+                    text: "a [b]".to_string(),
+                    url: "https://example.com/(x)".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_nested_markup() {
+        let text = "**bold *italic* ~~del~~** (spl)secret(spl) `a*b` (rol)7(rol) (chn)9(chn) (emj)smile(emj)[1/2]";
+        assert_eq!(
+            parse(text),
+            vec![
+                Node::Bold(vec![
+                    Node::Text("bold ".to_string()),
+                    Node::Italic(vec![Node::Text("italic".to_string())]),
+                    Node::Text(" ".to_string()),
+                    Node::Strikethrough(vec![Node::Text("del".to_string())]),
+                ]),
+                Node::Text(" ".to_string()),
+                Node::Spoiler(vec![Node::Text("secret".to_string())]),
+                Node::Text(" ".to_string()),
+                Node::Code("a*b".to_string()),
+                Node::Text(" ".to_string()),
+                Node::MentionRole("7".to_string()),
+                Node::Text(" ".to_string()),
+                Node::Channel("9".to_string()),
+                Node::Text(" ".to_string()),
+                Node::Emoji {
+                    name: "smile".to_string(),
+                    id: "1/2".to_string()
+                },
+            ]
+        );
+        assert_eq!(plain_text(text), "bold italic del secret a*b @7 #9 :smile:");
+    }
+
+    #[test]
+    fn parses_blocks() {
+        let text = KMarkdown::new()
+            .quote("quoted")
+            .divider()
+            .code_block("rust", "let a = 1;")
+            .text("done")
+            .build();
+        assert_eq!(
+            parse(&text),
+            vec![
+                Node::Quote(vec![Node::Text("quoted".to_string())]),
+                Node::Divider,
+                Node::CodeBlock {
+                    lang: "rust".to_string(),
+                    code: "let a = 1;\n".to_string()
+                },
+                Node::Text("\ndone".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unclosed_markup_is_text() {
+        assert_eq!(plain_text("(met)bot(met) /roll 2*3"), "@bot /roll 2*3");
+        assert_eq!(parse("a ** b"), vec![Node::Text("a ** b".to_string())]);
+        assert_eq!(
+            parse("**a *b*"),
+            vec![Node::Text("**a ".to_string()), Node::Italic(vec![Node::Text("b".to_string())])]
+        );
+        assert_eq!(parse("*a (spl)b*"), vec![Node::Italic(vec![Node::Text("a (spl)b".to_string())])]);
+    }
+
+    #[test]
+    fn unclosed_nested_markup_is_fast() {
+        let inputs = [
+            "> *\n".repeat(2000),
+            "[".repeat(8000),
+            "**(ins)~~(spl)*".repeat(1000),
+            "[a](`(emj)x(emj)(met)".repeat(500),
+        ];
+        let start = std::time::Instant::now();
+        for input in &inputs {
+            assert!(!parse(input).is_empty());
+        }
+        assert_eq!(plain_text(&inputs[1]), inputs[1]);
+        assert!(start.elapsed() < std::time::Duration::from_secs(2), "{:?}", start.elapsed());
+    }
+}
@@ -1,9 +1,19 @@
-pub mod ws;
-pub mod http;
+pub(crate) mod ws;
+pub(crate) mod webhook;
+mod http;
 pub mod objects;
 pub mod request;
 pub mod response;
 pub mod event;
+pub mod card;
+pub mod kmarkdown;
+pub mod permission;
+pub mod pagination;
+pub(crate) mod ratelimit;
+pub(crate) mod retry;
+
+#[cfg(test)]
+pub(crate) mod testing;
 
 mod bool_as_u8 {
     use serde::de::Error;
@@ -26,4 +36,18 @@ mod bool_as_u8 {
             other => Err(D::Error::invalid_value(serde::de::Unexpected::Unsigned(other as u64), &"zero or one")),
         }
     }
-}
\ No newline at end of file
+}
+
+mod option_bool_as_u8 {
+    use serde::Serializer;
+
+    pub fn serialize<S>(data: &Option<bool>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match data {
+            Some(data) => super::bool_as_u8::serialize(data, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+}
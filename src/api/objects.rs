@@ -1,90 +1,168 @@
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
-use super::bool_as_u8;
+use super::{bool_as_u8, permission::Permissions};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
-    id: String,
-    username: String,
-    nickname: String,
-    identify_num: String,
-    online: bool,
-    bot: bool,
-    status: UserStatus,
-    avatar: String,
-    vip_avatar: String,
-    mobile_verified: bool,
-    roles: Vec<u64>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
+    pub id: String,
+    pub username: String,
+    #[serde(default)]
+    pub nickname: String,
+    pub identify_num: String,
+    pub online: bool,
+    #[serde(default)]
+    pub bot: bool,
+    pub status: UserStatus,
+    pub avatar: String,
+    #[serde(default)]
+    pub vip_avatar: String,
+    #[serde(default)]
+    pub mobile_verified: bool,
+    #[serde(default)]
+    pub roles: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Guild {
-    id: String,
-    name: String,
-    topic: String,
-    user_id: String,
-    icon: String,
-    notify_type: NotifyType,
-    region: String,
+    pub id: String,
+    pub name: String,
+    pub topic: String,
+    // 服务器主
+    pub user_id: String,
+    pub icon: String,
+    pub notify_type: NotifyType,
+    pub region: String,
     #[serde(with = "bool_as_u8")]
-    enable_open: bool,
-    open_id: String,
-    default_channel_id: String,
-    welcome_channel_id: String,
-    roles: Vec<Role>,
-    channels: Vec<Channel>,
+    pub enable_open: bool,
+    pub open_id: String,
+    pub default_channel_id: String,
+    pub welcome_channel_id: String,
+    pub roles: Vec<Role>,
+    pub channels: Vec<Channel>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Role {
-    role_id: u64,
-    name: String,
-    color: u64,
-    position: u64,
+    pub role_id: u64,
+    pub name: String,
+    pub color: u64,
+    pub position: u64,
     #[serde(with = "bool_as_u8")]
-    hoist: bool,
+    pub hoist: bool,
     #[serde(with = "bool_as_u8")]
-    mentionable: bool,
-    permissions: u64,
+    pub mentionable: bool,
+    pub permissions: Permissions,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Channel {
-    id: String,
-    name: String,
-    user_id: String,
-    guild_id: String,
-    topic: String,
-    is_category: bool,
-    parent_id: String,
-    level: u32,
-    slow_mode: u32,
-    r#type: ChannelType,
-    permission_overwrites: Vec<PermissionOverwrite>,
-    permission_users: Vec<PermissionUser>,
-    #[serde(with = "bool_as_u8")]
-    permission_sync: bool,
-    has_password: bool,
+    pub id: String,
+    pub name: String,
+    pub user_id: String,
+    pub guild_id: String,
+    #[serde(default)]
+    pub topic: String,
+    pub is_category: bool,
+    #[serde(default)]
+    pub parent_id: String,
+    pub level: u32,
+    #[serde(default)]
+    pub slow_mode: u32,
+    #[serde(rename = "type")]
+    pub channel_type: ChannelType,
+    #[serde(default)]
+    pub permission_overwrites: Vec<PermissionOverwrite>,
+    #[serde(default)]
+    pub permission_users: Vec<PermissionUser>,
+    #[serde(with = "bool_as_u8", default)]
+    pub permission_sync: bool,
+    #[serde(default)]
+    pub has_password: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Quote {
-    id: String,
-    r#type: i32,
-    content: String,
-    create_at: i64,
-    author: User,
+    pub id: String,
+    #[serde(rename = "type")]
+    pub quote_type: MessageType,
+    pub content: String,
+    pub create_at: i64,
+    pub author: User,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Attachments {
-    r#type: String,
-    url: String,
-    name: String,
-    size: u64,
+    #[serde(rename = "type")]
+    pub attachments_type: String,
+    pub url: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub size: u64,
+}
+
+// 按数字序列化, KOOK 新增的类型会解析为 Unknown
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum MessageType {
+    Text,
+    Image,
+    Video,
+    File,
+    Audio,
+    KMarkdown,
+    Card,
+    Item,
+    System,
+    Unknown(u8),
+}
+
+impl From<u8> for MessageType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => MessageType::Text,
+            2 => MessageType::Image,
+            3 => MessageType::Video,
+            4 => MessageType::File,
+            8 => MessageType::Audio,
+            9 => MessageType::KMarkdown,
+            10 => MessageType::Card,
+            12 => MessageType::Item,
+            255 => MessageType::System,
+            x => MessageType::Unknown(x),
+        }
+    }
+}
+
+impl From<MessageType> for u8 {
+    fn from(value: MessageType) -> Self {
+        match value {
+            MessageType::Text => 1,
+            MessageType::Image => 2,
+            MessageType::Video => 3,
+            MessageType::File => 4,
+            MessageType::Audio => 8,
+            MessageType::KMarkdown => 9,
+            MessageType::Card => 10,
+            MessageType::Item => 12,
+            MessageType::System => 255,
+            MessageType::Unknown(x) => x,
+        }
+    }
+}
+
+impl Serialize for MessageType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8((*self).into())
+    }
+}
+
+impl<'de> Deserialize<'de> for MessageType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u8::deserialize(deserializer).map(Into::into)
+    }
 }
 
-#[derive(Serialize_repr, Deserialize_repr, PartialEq, Debug)]
+#[derive(Serialize_repr, Deserialize_repr, PartialEq, Eq, Clone, Copy, Debug)]
 #[repr(u8)]
 pub enum UserStatus {
     Normal0 = 0,
@@ -92,7 +170,7 @@ pub enum UserStatus {
     Ban = 10,
 }
 
-#[derive(Serialize_repr, Deserialize_repr, PartialEq, Debug)]
+#[derive(Serialize_repr, Deserialize_repr, PartialEq, Eq, Clone, Copy, Debug)]
 #[repr(u8)]
 pub enum NotifyType {
     Default = 0,
@@ -103,7 +181,7 @@ pub enum NotifyType {
 
 
 
-#[derive(Serialize_repr, Deserialize_repr, PartialEq, Debug)]
+#[derive(Serialize_repr, Deserialize_repr, PartialEq, Eq, Clone, Copy, Debug)]
 #[repr(u8)]
 pub enum ChannelType {
     None = 0,
@@ -111,16 +189,32 @@ pub enum ChannelType {
     Voice = 2,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PermissionOverwrite {
-    role_id: u64,
-    allow: i32,
-    deny: i32,
+    pub role_id: u64,
+    pub allow: Permissions,
+    pub deny: Permissions,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PermissionUser {
-    user: User,
-    allow: i32,
-    deny: i32,
+    pub user: User,
+    pub allow: Permissions,
+    pub deny: Permissions,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_type_keeps_unknown_codes() {
+        assert_eq!(serde_json::from_str::<MessageType>("9").unwrap(), MessageType::KMarkdown);
+        assert_eq!(serde_json::from_str::<MessageType>("7").unwrap(), MessageType::Unknown(7));
+        assert_eq!(serde_json::to_string(&MessageType::Unknown(7)).unwrap(), "7");
+        assert_eq!(serde_json::to_string(&MessageType::System).unwrap(), "255");
+        for code in 0..=u8::MAX {
+            assert_eq!(u8::from(MessageType::from(code)), code);
+        }
+    }
 }
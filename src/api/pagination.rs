@@ -0,0 +1,274 @@
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::{future::BoxFuture, Stream};
+use serde::de::DeserializeOwned;
+
+use super::response::{Page, PageMeta};
+use crate::{error::KookResult, Bot};
+
+// 分页参数, page 从 1 开始
+#[derive(Debug, Clone)]
+pub struct PageQuery {
+    pub page: u32,
+    pub page_size: u32,
+    // 排序字段, 例如 "id" 升序, "-id" 降序, None 使用服务端默认顺序
+    pub sort: Option<String>,
+}
+
+impl Default for PageQuery {
+    fn default() -> Self {
+        Self {
+            page: 1,
+            page_size: 50,
+            sort: None,
+        }
+    }
+}
+
+impl PageQuery {
+    pub fn with_page(mut self, page: u32) -> Self {
+        self.page = page;
+        self
+    }
+
+    pub fn with_page_size(mut self, page_size: u32) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    pub fn with_sort(mut self, sort: impl Into<String>) -> Self {
+        self.sort = Some(sort.into());
+        self
+    }
+
+    pub(crate) fn to_query(&self) -> Vec<(String, String)> {
+        let mut query = vec![("page".to_string(), self.page.to_string()), ("page_size".to_string(), self.page_size.to_string())];
+        if let Some(ref sort) = self.sort {
+            query.push(("sort".to_string(), sort.clone()));
+        }
+        query
+    }
+}
+
+// 按需请求下一页的列表流, meta 为最近一次请求到的分页信息
+pub struct Paginator<'a, T> {
+    bot: &'a Bot,
+    path: &'static str,
+    query: Vec<(String, String)>,
+    // 下一次请求的分页参数, None 表示已经取完
+    next: Option<PageQuery>,
+    items: VecDeque<T>,
+    meta: Option<PageMeta>,
+    fetching: Option<BoxFuture<'a, KookResult<Page<T>>>>,
+}
+
+// 不会对字段做 pin 投影
+impl<T> Unpin for Paginator<'_, T> {}
+
+impl<'a, T: DeserializeOwned + Send + 'a> Paginator<'a, T> {
+    pub(crate) fn new(bot: &'a Bot, path: &'static str, query: Vec<(String, String)>, page: PageQuery) -> Self {
+        Self {
+            bot,
+            path,
+            query,
+            next: Some(page),
+            items: VecDeque::new(),
+            meta: None,
+            fetching: None,
+        }
+    }
+
+    pub fn meta(&self) -> Option<&PageMeta> {
+        self.meta.as_ref()
+    }
+}
+
+impl<'a, T: DeserializeOwned + Send + 'a> Stream for Paginator<'a, T> {
+    type Item = KookResult<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(item) = this.items.pop_front() {
+                return Poll::Ready(Some(Ok(item)));
+            }
+            let fetching = match this.fetching {
+                Some(ref mut fetching) => fetching,
+                None => {
+                    let Some(ref page) = this.next else {
+                        return Poll::Ready(None);
+                    };
+                    let mut query = this.query.clone();
+                    query.extend(page.to_query());
+                    this.fetching.insert(Box::pin(this.bot.http_get_page(this.path, query)))
+                }
+            };
+            let ret = match fetching.as_mut().poll(cx) {
+                Poll::Ready(ret) => ret,
+                Poll::Pending => return Poll::Pending,
+            };
+            this.fetching = None;
+            match ret {
+                Ok(page) => {
+                    // 页码在本地递增, 不使用服务端返回的 page
+                    this.next = match this.next.take() {
+                        Some(next) if has_more(&next, &page) => next.page.checked_add(1).map(|page| PageQuery { page, ..next }),
+                        _ => None,
+                    };
+                    this.items.extend(page.items);
+                    this.meta = Some(page.meta);
+                }
+                Err(err) => {
+                    this.next = None;
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+        }
+    }
+}
+
+// 空页时结束, page_total 和 total 可能为 0, 都没有时按是否取满一页判断
+fn has_more<T>(query: &PageQuery, page: &Page<T>) -> bool {
+    let meta = &page.meta;
+    if page.items.is_empty() {
+        false
+    } else if meta.page_total > 0 {
+        i64::from(query.page) < i64::from(meta.page_total)
+    } else if meta.total > 0 {
+        i64::from(query.page) * i64::from(query.page_size) < i64::from(meta.total)
+    } else {
+        page.items.len() >= query.page_size as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use futures_util::{StreamExt, TryStreamExt};
+    use hyper::Response;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{api::testing::mock_server, url::http_api, Token};
+
+    // 共 total 条数据, 记录每次请求的 query
+    fn pages(total: i32, queries: Arc<Mutex<Vec<String>>>) -> Bot {
+        let url = mock_server(move |req| {
+            let query = req.uri().query().unwrap_or_default().to_string();
+            queries.lock().unwrap().push(query.clone());
+            let param = |name: &str| {
+                query
+                    .split('&')
+                    .find_map(|x| x.strip_prefix(&format!("{name}=")))
+                    .and_then(|x| x.parse::<i32>().ok())
+            };
+            let page = param("page").unwrap_or(1);
+            let page_size = param("page_size").unwrap_or(50);
+            let page_total = (total + page_size - 1) / page_size;
+            let items: Vec<i32> = ((page - 1) * page_size..(page * page_size).min(total)).collect();
+            let body = serde_json::json!({
+                "code": 0,
+                "message": "",
+                "data": {
+                    "items": items,
+                    "meta": {"page": page, "page_total": page_total, "page_size": page_size, "total": total},
+                    "sort": {}
+                }
+            });
+            Response::new(body.to_string().into())
+        });
+        Bot::builder(Token::Bot("test".to_string())).with_base_url(url).build()
+    }
+
+    #[tokio::test]
+    async fn fetches_pages_lazily() {
+        let queries = Arc::new(Mutex::new(Vec::new()));
+        let bot = pages(5, queries.clone());
+        let page = PageQuery::default().with_page_size(2).with_sort("-id");
+        let mut stream = bot.http_get_stream::<i32>(http_api::GUILD_LIST, &[("guild_id", "g")], page);
+        assert!(stream.meta().is_none());
+        assert_eq!(stream.next().await.unwrap().unwrap(), 0);
+        assert_eq!(queries.lock().unwrap().len(), 1);
+        assert_eq!(stream.meta().unwrap().page_total, 3);
+        let rest: Vec<i32> = stream.try_collect().await.unwrap();
+        assert_eq!(rest, vec![1, 2, 3, 4]);
+        assert_eq!(
+            *queries.lock().unwrap(),
+            vec![
+                "guild_id=g&page=1&page_size=2&sort=-id",
+                "guild_id=g&page=2&page_size=2&sort=-id",
+                "guild_id=g&page=3&page_size=2&sort=-id"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn starts_from_page() {
+        let bot = pages(5, Default::default());
+        let items: Vec<i32> = bot
+            .http_get_stream::<i32>(http_api::GUILD_LIST, &[], PageQuery::default().with_page(2).with_page_size(2))
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(items, vec![2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn stops_on_empty_list() {
+        let queries = Arc::new(Mutex::new(Vec::new()));
+        let bot = pages(0, queries.clone());
+        let items: Vec<i32> = bot.http_get_page_all(http_api::GUILD_LIST, &[]).await.unwrap();
+        assert!(items.is_empty());
+        assert_eq!(queries.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn error_ends_stream() {
+        let url = mock_server(|_| Response::new(r#"{"code":40100,"message":"no permission","data":{}}"#.into()));
+        let bot = Bot::builder(Token::Bot("test".to_string())).with_base_url(url).build();
+        let mut stream = bot.http_get_stream::<Value>(http_api::GUILD_LIST, &[], PageQuery::default());
+        assert!(matches!(stream.next().await, Some(Err(crate::KookError::Api { code: 40100, .. }))));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn continues_without_page_total() {
+        let queries = Arc::new(Mutex::new(Vec::new()));
+        let record = queries.clone();
+        let url = mock_server(move |req| {
+            let query = req.uri().query().unwrap_or_default().to_string();
+            let items = match query.contains("page=3&") {
+                true => vec![4],
+                false => vec![0, 1],
+            };
+            record.lock().unwrap().push(query);
+            // page 总是返回 1, page_total 和 total 为 0
+            let body = serde_json::json!({
+                "code": 0,
+                "message": "",
+                "data": {
+                    "items": items,
+                    "meta": {"page": 1, "page_total": 0, "page_size": 2, "total": 0},
+                    "sort": {}
+                }
+            });
+            Response::new(body.to_string().into())
+        });
+        let bot = Bot::builder(Token::Bot("test".to_string())).with_base_url(url).build();
+        let items: Vec<i32> = bot
+            .http_get_stream::<i32>(http_api::GUILD_LIST, &[], PageQuery::default().with_page_size(2))
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(items, vec![0, 1, 0, 1, 4]);
+        assert_eq!(
+            *queries.lock().unwrap(),
+            vec!["page=1&page_size=2", "page=2&page_size=2", "page=3&page_size=2"]
+        );
+    }
+}
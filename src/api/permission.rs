@@ -0,0 +1,204 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::objects::{Channel, Guild, PermissionOverwrite, PermissionUser, Role};
+
+bitflags::bitflags! {
+    // 权限值, 每一位对应一项权限
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct Permissions: u64 {
+        // 管理员, 拥有所有权限
+        const ADMINISTRATOR = 1 << 0;
+        const MANAGE_GUILD = 1 << 1;
+        const VIEW_AUDIT_LOG = 1 << 2;
+        const CREATE_INVITE = 1 << 3;
+        const MANAGE_INVITE = 1 << 4;
+        const MANAGE_CHANNELS = 1 << 5;
+        const KICK_MEMBERS = 1 << 6;
+        const BAN_MEMBERS = 1 << 7;
+        const MANAGE_EMOJIS = 1 << 8;
+        const CHANGE_NICKNAME = 1 << 9;
+        const MANAGE_ROLES = 1 << 10;
+        const VIEW_CHANNELS = 1 << 11;
+        const SEND_MESSAGES = 1 << 12;
+        const MANAGE_MESSAGES = 1 << 13;
+        const ATTACH_FILES = 1 << 14;
+        const CONNECT_VOICE = 1 << 15;
+        const MANAGE_VOICE = 1 << 16;
+        const MENTION_EVERYONE = 1 << 17;
+        const ADD_REACTIONS = 1 << 18;
+        const FOLLOW_REACTIONS = 1 << 19;
+        const PASSIVE_CONNECT_VOICE = 1 << 20;
+        const PUSH_TO_TALK = 1 << 21;
+        const USE_VOICE_ACTIVITY = 1 << 22;
+        const SPEAK = 1 << 23;
+        const DEAFEN_MEMBERS = 1 << 24;
+        const MUTE_MEMBERS = 1 << 25;
+        const MANAGE_NICKNAMES = 1 << 26;
+        const PLAY_MUSIC = 1 << 27;
+        const SCREEN_SHARE = 1 << 28;
+    }
+}
+
+// 接口中权限都是整数, 未知的位原样保留
+impl Serialize for Permissions {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.bits())
+    }
+}
+
+impl<'de> Deserialize<'de> for Permissions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Permissions::from_bits_retain(u64::deserialize(deserializer)?))
+    }
+}
+
+// 全体成员角色的 role_id
+pub const EVERYONE_ROLE_ID: u64 = 0;
+
+// 服务器级别的权限: 服务器主拥有全部权限, 其余为全体成员角色与用户所有角色的并集
+pub fn base_permissions(owner_id: &str, roles: &[Role], user_id: &str, user_roles: &[u64]) -> Permissions {
+    if user_id == owner_id {
+        return Permissions::all();
+    }
+    let permissions = roles
+        .iter()
+        .filter(|role| role.role_id == EVERYONE_ROLE_ID || user_roles.contains(&role.role_id))
+        .fold(Permissions::empty(), |acc, role| acc | role.permissions);
+    if permissions.contains(Permissions::ADMINISTRATOR) {
+        return Permissions::all();
+    }
+    permissions
+}
+
+// 依次应用频道中全体成员角色, 用户所有角色, 用户自身的权限覆盖
+pub fn apply_overwrites(
+    base: Permissions,
+    user_id: &str,
+    user_roles: &[u64],
+    overwrites: &[PermissionOverwrite],
+    users: &[PermissionUser],
+) -> Permissions {
+    if base.contains(Permissions::ADMINISTRATOR) {
+        return Permissions::all();
+    }
+    let mut permissions = base;
+    if let Some(everyone) = overwrites.iter().find(|x| x.role_id == EVERYONE_ROLE_ID) {
+        permissions = (permissions - everyone.deny) | everyone.allow;
+    }
+    let (allow, deny) = overwrites
+        .iter()
+        .filter(|x| x.role_id != EVERYONE_ROLE_ID && user_roles.contains(&x.role_id))
+        .fold((Permissions::empty(), Permissions::empty()), |(allow, deny), x| (allow | x.allow, deny | x.deny));
+    permissions = (permissions - deny) | allow;
+    if let Some(user) = users.iter().find(|x| x.user.id == user_id) {
+        permissions = (permissions - user.deny) | user.allow;
+    }
+    permissions
+}
+
+// 用户在服务器 (不传频道时) 或频道中的最终权限
+pub fn effective_permissions(guild: &Guild, user_id: &str, user_roles: &[u64], channel: Option<&Channel>) -> Permissions {
+    let base = base_permissions(&guild.user_id, &guild.roles, user_id, user_roles);
+    match channel {
+        Some(channel) => apply_overwrites(base, user_id, user_roles, &channel.permission_overwrites, &channel.permission_users),
+        None => base,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(role_id: u64, permissions: Permissions) -> Role {
+        Role {
+            role_id,
+            name: role_id.to_string(),
+            color: 0,
+            position: role_id,
+            hoist: false,
+            mentionable: false,
+            permissions,
+        }
+    }
+
+    fn overwrite(role_id: u64, allow: Permissions, deny: Permissions) -> PermissionOverwrite {
+        PermissionOverwrite { role_id, allow, deny }
+    }
+
+    fn user_overwrite(user_id: &str, allow: Permissions, deny: Permissions) -> PermissionUser {
+        let user = serde_json::json!({
+            "id": user_id, "username": user_id, "identify_num": "0001", "online": true, "status": 0, "avatar": ""
+        });
+        PermissionUser {
+            user: serde_json::from_value(user).unwrap(),
+            allow,
+            deny,
+        }
+    }
+
+    fn roles() -> Vec<Role> {
+        vec![
+            role(EVERYONE_ROLE_ID, Permissions::VIEW_CHANNELS | Permissions::SEND_MESSAGES),
+            role(1, Permissions::MANAGE_MESSAGES),
+            role(2, Permissions::ADMINISTRATOR),
+        ]
+    }
+
+    #[test]
+    fn owner_and_admin_have_everything() {
+        assert_eq!(base_permissions("owner", &roles(), "owner", &[]), Permissions::all());
+        assert_eq!(base_permissions("owner", &roles(), "user", &[2]), Permissions::all());
+        let denied = [overwrite(EVERYONE_ROLE_ID, Permissions::empty(), Permissions::all())];
+        assert_eq!(
+            apply_overwrites(base_permissions("owner", &roles(), "user", &[2]), "user", &[2], &denied, &[]),
+            Permissions::all()
+        );
+    }
+
+    #[test]
+    fn base_merges_everyone_and_member_roles() {
+        assert_eq!(
+            base_permissions("owner", &roles(), "user", &[]),
+            Permissions::VIEW_CHANNELS | Permissions::SEND_MESSAGES
+        );
+        assert_eq!(
+            base_permissions("owner", &roles(), "user", &[1]),
+            Permissions::VIEW_CHANNELS | Permissions::SEND_MESSAGES | Permissions::MANAGE_MESSAGES
+        );
+    }
+
+    #[test]
+    fn overwrites_apply_everyone_then_roles_then_user() {
+        let base = base_permissions("owner", &roles(), "user", &[1]);
+        let overwrites = [
+            overwrite(EVERYONE_ROLE_ID, Permissions::empty(), Permissions::SEND_MESSAGES | Permissions::VIEW_CHANNELS),
+            overwrite(1, Permissions::SEND_MESSAGES, Permissions::empty()),
+            overwrite(3, Permissions::ATTACH_FILES, Permissions::empty()),
+        ];
+        assert_eq!(
+            apply_overwrites(base, "user", &[1], &overwrites, &[]),
+            Permissions::SEND_MESSAGES | Permissions::MANAGE_MESSAGES
+        );
+
+        let users = [user_overwrite("user", Permissions::VIEW_CHANNELS, Permissions::SEND_MESSAGES)];
+        assert_eq!(
+            apply_overwrites(base, "user", &[1], &overwrites, &users),
+            Permissions::VIEW_CHANNELS | Permissions::MANAGE_MESSAGES
+        );
+        assert_eq!(apply_overwrites(base, "other", &[], &overwrites, &users), Permissions::MANAGE_MESSAGES);
+    }
+
+    #[test]
+    fn serializes_as_integer() {
+        let permissions = Permissions::VIEW_CHANNELS | Permissions::SEND_MESSAGES;
+        assert_eq!(serde_json::to_string(&permissions).unwrap(), "6144");
+        assert_eq!(serde_json::from_str::<Permissions>("6144").unwrap(), permissions);
+    }
+
+    #[test]
+    fn keeps_unknown_bits() {
+        let permissions: Permissions = serde_json::from_str(&(1u64 << 40 | 1).to_string()).unwrap();
+        assert!(permissions.contains(Permissions::ADMINISTRATOR));
+        assert_eq!(permissions.bits(), 1 << 40 | 1);
+    }
+}
@@ -0,0 +1,203 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use tokio::time::Instant;
+
+const LIMIT: &str = "X-Rate-Limit-Limit";
+const REMAINING: &str = "X-Rate-Limit-Remaining";
+const RESET: &str = "X-Rate-Limit-Reset";
+const BUCKET: &str = "X-Rate-Limit-Bucket";
+const GLOBAL: &str = "X-Rate-Limit-Global";
+
+// 429 没有带重置时间时的等待时间
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+// 重置时间的上限, 异常的响应头不会导致长时间等待
+const MAX_RESET: Duration = Duration::from_secs(60);
+
+// 状态码为 200 时通过响应体中的 code 表示被限速
+const LIMITED_CODE: i64 = 40000;
+
+#[derive(Deserialize)]
+struct Code {
+    code: i64,
+}
+
+pub(crate) fn limited_body(body: &str) -> bool {
+    body.contains("40000") && serde_json::from_str::<Code>(body).is_ok_and(|x| x.code == LIMITED_CODE)
+}
+
+#[derive(Debug)]
+struct Bucket {
+    remaining: u32,
+    reset_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    // 接口路径到限速桶名的映射, 多个接口可能共用一个桶
+    routes: HashMap<String, String>,
+    buckets: HashMap<String, Bucket>,
+    global_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    pub(crate) max_retries: u32,
+    state: Mutex<State>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+impl RateLimiter {
+    pub(crate) fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            state: Mutex::default(),
+        }
+    }
+
+    // 桶内没有剩余次数时等到重置
+    pub(crate) async fn acquire(&self, route: &str) {
+        loop {
+            let wait = self.try_acquire(route);
+            match wait {
+                Some(deadline) => {
+                    tracing::debug!("rate limited route:{} wait:{:?}", route, deadline - Instant::now());
+                    tokio::time::sleep_until(deadline).await
+                }
+                None => return,
+            }
+        }
+    }
+
+    fn try_acquire(&self, route: &str) -> Option<Instant> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match state.global_until {
+            Some(until) if until > now => return Some(until),
+            Some(_) => state.global_until = None,
+            None => {}
+        }
+        let name = state.routes.get(route).cloned().unwrap_or_else(|| route.to_string());
+        let bucket = state.buckets.get_mut(&name)?;
+        if bucket.reset_at <= now {
+            state.buckets.remove(&name);
+            return None;
+        }
+        if bucket.remaining == 0 {
+            return Some(bucket.reset_at);
+        }
+        bucket.remaining -= 1;
+        None
+    }
+
+    // 根据响应头更新限速状态, 被限速时返回需要等待的时间
+    pub(crate) fn update(&self, route: &str, limited: bool, headers: &HeaderMap) -> Option<Duration> {
+        let header = |name: &str| headers.get(name).and_then(|x| x.to_str().ok());
+        // 负数和 nan 视为没有重置时间, 过大的值按上限处理
+        let reset = header(RESET)
+            .and_then(|x| x.parse::<f64>().ok())
+            .filter(|x| *x >= 0.0)
+            .map(|x| Duration::try_from_secs_f64(x).unwrap_or(MAX_RESET).min(MAX_RESET));
+        let remaining = header(REMAINING).and_then(|x| x.parse::<u32>().ok());
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let name = match header(BUCKET) {
+            Some(bucket) => {
+                state.routes.insert(route.to_string(), bucket.to_string());
+                bucket.to_string()
+            }
+            None => state.routes.get(route).cloned().unwrap_or_else(|| route.to_string()),
+        };
+        if limited {
+            let retry_after = reset.unwrap_or(DEFAULT_RETRY_AFTER);
+            state.buckets.insert(
+                name,
+                Bucket {
+                    remaining: 0,
+                    reset_at: now + retry_after,
+                },
+            );
+            if header(GLOBAL).is_some() {
+                state.global_until = Some(now + retry_after);
+            }
+            return Some(retry_after);
+        }
+        if let (Some(remaining), Some(reset)) = (remaining, reset) {
+            tracing::trace!("rate limit bucket:{} limit:{:?} remaining:{}", name, header(LIMIT), remaining);
+            state.buckets.insert(
+                name,
+                Bucket {
+                    remaining,
+                    reset_at: now + reset,
+                },
+            );
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn routes_share_bucket() {
+        let limiter = RateLimiter::default();
+        let headers = headers(&[(REMAINING, "1"), (RESET, "60"), (BUCKET, "message/create")]);
+        assert!(limiter.update("/a", false, &headers).is_none());
+        assert!(limiter.update("/b", false, &headers).is_none());
+        assert!(limiter.try_acquire("/a").is_none());
+        assert!(limiter.try_acquire("/b").is_some());
+    }
+
+    #[test]
+    fn global_limit_blocks_every_route() {
+        let limiter = RateLimiter::default();
+        let headers = headers(&[(RESET, "60"), (GLOBAL, "1")]);
+        assert_eq!(limiter.update("/a", true, &headers), Some(Duration::from_secs(60)));
+        assert!(limiter.try_acquire("/other").is_some());
+    }
+
+    #[test]
+    fn expired_bucket_is_released() {
+        let limiter = RateLimiter::default();
+        let headers = headers(&[(REMAINING, "0"), (RESET, "0")]);
+        limiter.update("/a", false, &headers);
+        assert!(limiter.try_acquire("/a").is_none());
+    }
+
+    #[test]
+    fn invalid_reset_is_ignored_or_capped() {
+        let limiter = RateLimiter::default();
+        for reset in ["-1", "nan", "inf", "1e300"] {
+            let headers = headers(&[(RESET, reset)]);
+            assert!(limiter.update("/a", true, &headers).is_some_and(|x| x <= MAX_RESET));
+        }
+        let headers = headers(&[(REMAINING, "0"), (RESET, "1e300")]);
+        limiter.update("/b", false, &headers);
+        assert!(limiter.try_acquire("/b").is_some_and(|x| x <= Instant::now() + MAX_RESET));
+    }
+
+    #[test]
+    fn detects_limited_body() {
+        assert!(limited_body(r#"{"code":40000,"message":"请求过于频繁","data":{}}"#));
+        assert!(!limited_body(r#"{"code":0,"message":"","data":{"id":"40000"}}"#));
+        assert!(!limited_body("40000"));
+    }
+}
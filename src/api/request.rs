@@ -1,4 +1,13 @@
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
+use tokio::io::AsyncRead;
+
+use super::{
+    objects::{ChannelType, MessageType},
+    pagination::PageQuery,
+    permission::Permissions,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GuildNickname<'a> {
@@ -12,14 +21,678 @@ pub struct GuildLeave<'a> {
     pub(crate) guild_id: &'a str,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct MessageCreate<'a> {
     #[serde(rename = "type")]
-    pub(crate) message_create_type: i64,
+    pub message_type: MessageType,
 
     #[serde(rename = "target_id")]
+    pub target_id: &'a str,
+
+    #[serde(rename = "content")]
+    pub content: &'a str,
+
+    #[serde(rename = "quote", skip_serializing_if = "Option::is_none")]
+    pub quote: Option<&'a str>,
+
+    #[serde(rename = "nonce", skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<&'a str>,
+
+    #[serde(rename = "temp_target_id", skip_serializing_if = "Option::is_none")]
+    pub temp_target_id: Option<&'a str>,
+}
+
+impl<'a> MessageCreate<'a> {
+    pub fn new(target_id: &'a str, content: &'a str) -> Self {
+        Self {
+            message_type: MessageType::Text,
+            target_id,
+            content,
+            quote: None,
+            nonce: None,
+            temp_target_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageUpdate<'a> {
+    #[serde(rename = "msg_id")]
+    pub msg_id: &'a str,
+
+    #[serde(rename = "content")]
+    pub content: &'a str,
+
+    #[serde(rename = "quote", skip_serializing_if = "Option::is_none")]
+    pub quote: Option<&'a str>,
+
+    // 只能更新发给该用户的临时消息
+    #[serde(rename = "temp_target_id", skip_serializing_if = "Option::is_none")]
+    pub temp_target_id: Option<&'a str>,
+}
+
+impl<'a> MessageUpdate<'a> {
+    pub fn new(msg_id: &'a str, content: &'a str) -> Self {
+        Self {
+            msg_id,
+            content,
+            quote: None,
+            temp_target_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageDelete<'a> {
+    pub(crate) msg_id: &'a str,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageListFlag {
+    Before,
+    Around,
+    After,
+}
+
+impl MessageListFlag {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            MessageListFlag::Before => "before",
+            MessageListFlag::Around => "around",
+            MessageListFlag::After => "after",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageList<'a> {
+    pub target_id: &'a str,
+    // 参考消息, 不填时查询最新的消息
+    pub msg_id: Option<&'a str>,
+    // 只查询置顶消息
+    pub pin: bool,
+    pub flag: Option<MessageListFlag>,
+    pub page_size: Option<u32>,
+}
+
+impl<'a> MessageList<'a> {
+    pub fn new(target_id: &'a str) -> Self {
+        Self {
+            target_id,
+            msg_id: None,
+            pin: false,
+            flag: None,
+            page_size: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageReaction<'a> {
+    pub(crate) msg_id: &'a str,
+    pub(crate) emoji: &'a str,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageDeleteReaction<'a> {
+    pub(crate) msg_id: &'a str,
+    pub(crate) emoji: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) user_id: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserChatCreate<'a> {
     pub(crate) target_id: &'a str,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserChatDelete<'a> {
+    pub(crate) chat_code: &'a str,
+}
+
+// 私信的对象, 可以是私信会话 code, 也可以是对方的用户 id
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum DirectTarget<'a> {
+    #[serde(rename = "chat_code")]
+    ChatCode(&'a str),
+    #[serde(rename = "target_id")]
+    TargetId(&'a str),
+}
+
+impl<'a> DirectTarget<'a> {
+    pub(crate) fn as_query(&self) -> (&'static str, &'a str) {
+        match *self {
+            DirectTarget::ChatCode(chat_code) => ("chat_code", chat_code),
+            DirectTarget::TargetId(target_id) => ("target_id", target_id),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DirectMessageCreate<'a> {
+    #[serde(rename = "type")]
+    pub message_type: MessageType,
+
+    #[serde(flatten)]
+    pub target: DirectTarget<'a>,
+
+    #[serde(rename = "content")]
+    pub content: &'a str,
+
+    #[serde(rename = "quote", skip_serializing_if = "Option::is_none")]
+    pub quote: Option<&'a str>,
+
+    #[serde(rename = "nonce", skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<&'a str>,
+}
+
+impl<'a> DirectMessageCreate<'a> {
+    pub fn new(target: DirectTarget<'a>, content: &'a str) -> Self {
+        Self {
+            message_type: MessageType::Text,
+            target,
+            content,
+            quote: None,
+            nonce: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DirectMessageUpdate<'a> {
+    #[serde(rename = "msg_id")]
+    pub msg_id: &'a str,
 
     #[serde(rename = "content")]
-    pub(crate) content: &'a str,
+    pub content: &'a str,
+
+    #[serde(rename = "quote", skip_serializing_if = "Option::is_none")]
+    pub quote: Option<&'a str>,
+}
+
+impl<'a> DirectMessageUpdate<'a> {
+    pub fn new(msg_id: &'a str, content: &'a str) -> Self {
+        Self { msg_id, content, quote: None }
+    }
+}
+
+#[derive(Debug)]
+pub struct DirectMessageList<'a> {
+    pub target: DirectTarget<'a>,
+    // 参考消息, 不填时查询最新的消息
+    pub msg_id: Option<&'a str>,
+    pub flag: Option<MessageListFlag>,
+    pub page_size: Option<u32>,
+}
+
+impl<'a> DirectMessageList<'a> {
+    pub fn new(target: DirectTarget<'a>) -> Self {
+        Self {
+            target,
+            msg_id: None,
+            flag: None,
+            page_size: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChannelCreate<'a> {
+    #[serde(rename = "guild_id")]
+    pub guild_id: &'a str,
+
+    #[serde(rename = "name")]
+    pub name: &'a str,
+
+    #[serde(rename = "parent_id", skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<&'a str>,
+
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub channel_type: Option<ChannelType>,
+
+    // 语音频道人数限制
+    #[serde(rename = "limit_amount", skip_serializing_if = "Option::is_none")]
+    pub limit_amount: Option<u32>,
+
+    // 语音音质, 1 流畅, 2 正常, 3 高质量
+    #[serde(rename = "voice_quality", skip_serializing_if = "Option::is_none")]
+    pub voice_quality: Option<&'a str>,
+
+    #[serde(rename = "is_category", with = "super::bool_as_u8", skip_serializing_if = "std::ops::Not::not")]
+    pub is_category: bool,
+}
+
+impl<'a> ChannelCreate<'a> {
+    pub fn new(guild_id: &'a str, name: &'a str) -> Self {
+        Self {
+            guild_id,
+            name,
+            parent_id: None,
+            channel_type: None,
+            limit_amount: None,
+            voice_quality: None,
+            is_category: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChannelUpdate<'a> {
+    #[serde(rename = "channel_id")]
+    pub channel_id: &'a str,
+
+    #[serde(rename = "name", skip_serializing_if = "Option::is_none")]
+    pub name: Option<&'a str>,
+
+    #[serde(rename = "level", skip_serializing_if = "Option::is_none")]
+    pub level: Option<u32>,
+
+    #[serde(rename = "parent_id", skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<&'a str>,
+
+    #[serde(rename = "topic", skip_serializing_if = "Option::is_none")]
+    pub topic: Option<&'a str>,
+
+    // 慢速模式, 单位毫秒
+    #[serde(rename = "slow_mode", skip_serializing_if = "Option::is_none")]
+    pub slow_mode: Option<u32>,
+
+    #[serde(rename = "limit_amount", skip_serializing_if = "Option::is_none")]
+    pub limit_amount: Option<u32>,
+
+    #[serde(rename = "voice_quality", skip_serializing_if = "Option::is_none")]
+    pub voice_quality: Option<&'a str>,
+
+    #[serde(rename = "password", skip_serializing_if = "Option::is_none")]
+    pub password: Option<&'a str>,
+}
+
+impl<'a> ChannelUpdate<'a> {
+    pub fn new(channel_id: &'a str) -> Self {
+        Self {
+            channel_id,
+            name: None,
+            level: None,
+            parent_id: None,
+            topic: None,
+            slow_mode: None,
+            limit_amount: None,
+            voice_quality: None,
+            password: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChannelDelete<'a> {
+    pub(crate) channel_id: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChannelMoveUser<'a> {
+    pub(crate) target_id: &'a str,
+    pub(crate) user_ids: &'a [&'a str],
+}
+
+// 频道权限的设置对象, 角色或者用户
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelRoleTarget<'a> {
+    Role(u64),
+    User(&'a str),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChannelRole<'a> {
+    pub(crate) channel_id: &'a str,
+    #[serde(rename = "type")]
+    pub(crate) target_type: &'static str,
+    pub(crate) value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) allow: Option<Permissions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) deny: Option<Permissions>,
+}
+
+impl<'a> ChannelRole<'a> {
+    pub(crate) fn new(channel_id: &'a str, target: ChannelRoleTarget<'_>) -> Self {
+        let (target_type, value) = match target {
+            ChannelRoleTarget::Role(role_id) => ("role_id", role_id.to_string()),
+            ChannelRoleTarget::User(user_id) => ("user_id", user_id.to_string()),
+        };
+        Self {
+            channel_id,
+            target_type,
+            value,
+            allow: None,
+            deny: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct GuildRoleCreate<'a> {
+    pub(crate) guild_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) name: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GuildRoleUpdate<'a> {
+    #[serde(rename = "guild_id")]
+    pub guild_id: &'a str,
+
+    #[serde(rename = "role_id")]
+    pub role_id: u64,
+
+    #[serde(rename = "name", skip_serializing_if = "Option::is_none")]
+    pub name: Option<&'a str>,
+
+    #[serde(rename = "color", skip_serializing_if = "Option::is_none")]
+    pub color: Option<u64>,
+
+    // 是否在用户列表中单独显示
+    #[serde(rename = "hoist", with = "super::option_bool_as_u8", skip_serializing_if = "Option::is_none")]
+    pub hoist: Option<bool>,
+
+    #[serde(rename = "mentionable", with = "super::option_bool_as_u8", skip_serializing_if = "Option::is_none")]
+    pub mentionable: Option<bool>,
+
+    #[serde(rename = "permissions", skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<Permissions>,
+}
+
+impl<'a> GuildRoleUpdate<'a> {
+    pub fn new(guild_id: &'a str, role_id: u64) -> Self {
+        Self {
+            guild_id,
+            role_id,
+            name: None,
+            color: None,
+            hoist: None,
+            mentionable: None,
+            permissions: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GuildRoleDelete<'a> {
+    pub(crate) guild_id: &'a str,
+    pub(crate) role_id: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GuildRoleGrant<'a> {
+    pub(crate) guild_id: &'a str,
+    pub(crate) user_id: &'a str,
+    pub(crate) role_id: u64,
+}
+
+pub enum AssetSource {
+    Path(PathBuf),
+    Bytes(Vec<u8>),
+    Reader(Box<dyn AsyncRead + Send + Sync + Unpin>),
+}
+
+impl std::fmt::Debug for AssetSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssetSource::Path(path) => f.debug_tuple("Path").field(path).finish(),
+            AssetSource::Bytes(bytes) => f.debug_tuple("Bytes").field(&bytes.len()).finish(),
+            AssetSource::Reader(_) => f.write_str("Reader"),
+        }
+    }
+}
+
+// 上传的媒体文件, 文件和读取器以流的方式上传
+#[derive(Debug)]
+pub struct Asset {
+    pub source: AssetSource,
+    pub file_name: String,
+    // 不填时根据文件后缀推断
+    pub content_type: Option<String>,
+}
+
+impl Asset {
+    pub fn path(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let file_name = path.file_name().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default();
+        Self {
+            source: AssetSource::Path(path),
+            file_name,
+            content_type: None,
+        }
+    }
+
+    pub fn bytes(file_name: impl Into<String>, bytes: impl Into<Vec<u8>>) -> Self {
+        Self {
+            source: AssetSource::Bytes(bytes.into()),
+            file_name: file_name.into(),
+            content_type: None,
+        }
+    }
+
+    pub fn reader(file_name: impl Into<String>, reader: impl AsyncRead + Send + Sync + Unpin + 'static) -> Self {
+        Self {
+            source: AssetSource::Reader(Box::new(reader)),
+            file_name: file_name.into(),
+            content_type: None,
+        }
+    }
+
+    pub fn with_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    pub(crate) fn mime(&self) -> &str {
+        if let Some(ref content_type) = self.content_type {
+            return content_type;
+        }
+        let ext = self.file_name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_default();
+        match ext.as_str() {
+            "png" => "image/png",
+            "jpg" | "jpeg" => "image/jpeg",
+            "gif" => "image/gif",
+            "webp" => "image/webp",
+            "mp4" => "video/mp4",
+            "mov" => "video/quicktime",
+            "mp3" => "audio/mpeg",
+            "txt" => "text/plain",
+            "pdf" => "application/pdf",
+            "zip" => "application/zip",
+            _ => "application/octet-stream",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "0",
+            SortOrder::Desc => "1",
+        }
+    }
+}
+
+// guild/user-list 的查询条件, 未设置的条件不会发送
+#[derive(Debug, Clone)]
+pub struct GuildUserQuery {
+    pub(crate) guild_id: String,
+    pub(crate) channel_id: Option<String>,
+    pub(crate) search: Option<String>,
+    pub(crate) role_id: Option<u64>,
+    pub(crate) mobile_verified: Option<bool>,
+    pub(crate) active_time: Option<SortOrder>,
+    pub(crate) joined_at: Option<SortOrder>,
+    pub(crate) filter_user_id: Option<String>,
+    pub(crate) page: PageQuery,
+}
+
+impl GuildUserQuery {
+    pub fn new(guild_id: impl Into<String>) -> Self {
+        Self {
+            guild_id: guild_id.into(),
+            channel_id: None,
+            search: None,
+            role_id: None,
+            mobile_verified: None,
+            active_time: None,
+            joined_at: None,
+            filter_user_id: None,
+            page: PageQuery::default(),
+        }
+    }
+
+    // 只返回该频道内的用户
+    pub fn with_channel_id(mut self, channel_id: impl Into<String>) -> Self {
+        self.channel_id = Some(channel_id.into());
+        self
+    }
+
+    // 按用户名或昵称搜索
+    pub fn with_search(mut self, search: impl Into<String>) -> Self {
+        self.search = Some(search.into());
+        self
+    }
+
+    pub fn with_role_id(mut self, role_id: u64) -> Self {
+        self.role_id = Some(role_id);
+        self
+    }
+
+    pub fn with_mobile_verified(mut self, mobile_verified: bool) -> Self {
+        self.mobile_verified = Some(mobile_verified);
+        self
+    }
+
+    pub fn sort_by_active_time(mut self, order: SortOrder) -> Self {
+        self.active_time = Some(order);
+        self
+    }
+
+    pub fn sort_by_joined_at(mut self, order: SortOrder) -> Self {
+        self.joined_at = Some(order);
+        self
+    }
+
+    // 只查询指定用户, 可用于判断用户是否在服务器内
+    pub fn with_filter_user_id(mut self, user_id: impl Into<String>) -> Self {
+        self.filter_user_id = Some(user_id.into());
+        self
+    }
+
+    pub fn with_page(mut self, page: PageQuery) -> Self {
+        self.page = page;
+        self
+    }
+
+    pub(crate) fn to_query(&self) -> Vec<(String, String)> {
+        let mut query = vec![("guild_id".to_string(), self.guild_id.clone())];
+        let mut push = |key: &str, value: Option<String>| {
+            if let Some(value) = value {
+                query.push((key.to_string(), value));
+            }
+        };
+        push("channel_id", self.channel_id.clone());
+        push("search", self.search.clone());
+        push("role_id", self.role_id.map(|x| x.to_string()));
+        push("mobile_verified", self.mobile_verified.map(|x| (x as u8).to_string()));
+        push("active_time", self.active_time.map(|x| x.as_str().to_string()));
+        push("joined_at", self.joined_at.map(|x| x.as_str().to_string()));
+        push("filter_user_id", self.filter_user_id.clone());
+        query
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_create_skips_empty_options() {
+        let req = MessageCreate::new("channel", "hello");
+        assert_eq!(
+            serde_json::to_value(&req).unwrap(),
+            serde_json::json!({"type": 1, "target_id": "channel", "content": "hello"})
+        );
+        let req = MessageCreate {
+            message_type: MessageType::KMarkdown,
+            quote: Some("msg"),
+            ..MessageCreate::new("channel", "hello")
+        };
+        assert_eq!(
+            serde_json::to_value(&req).unwrap(),
+            serde_json::json!({"type": 9, "target_id": "channel", "content": "hello", "quote": "msg"})
+        );
+    }
+
+    #[test]
+    fn direct_message_create_flattens_target() {
+        let req = DirectMessageCreate::new(DirectTarget::ChatCode("code"), "hello");
+        assert_eq!(
+            serde_json::to_value(&req).unwrap(),
+            serde_json::json!({"type": 1, "chat_code": "code", "content": "hello"})
+        );
+        let req = DirectMessageCreate::new(DirectTarget::TargetId("user"), "hello");
+        assert_eq!(
+            serde_json::to_value(&req).unwrap(),
+            serde_json::json!({"type": 1, "target_id": "user", "content": "hello"})
+        );
+    }
+
+    #[test]
+    fn channel_role_uses_type_and_value() {
+        let req = ChannelRole {
+            allow: Some(Permissions::VIEW_CHANNELS),
+            deny: Some(Permissions::empty()),
+            ..ChannelRole::new("channel", ChannelRoleTarget::Role(42))
+        };
+        assert_eq!(
+            serde_json::to_value(&req).unwrap(),
+            serde_json::json!({"channel_id": "channel", "type": "role_id", "value": "42", "allow": 2048, "deny": 0})
+        );
+        let req = ChannelRole::new("channel", ChannelRoleTarget::User("user"));
+        assert_eq!(
+            serde_json::to_value(&req).unwrap(),
+            serde_json::json!({"channel_id": "channel", "type": "user_id", "value": "user"})
+        );
+    }
+
+    #[test]
+    fn guild_role_update_skips_unchanged_fields() {
+        let req = GuildRoleUpdate {
+            hoist: Some(true),
+            permissions: Some(Permissions::SEND_MESSAGES),
+            ..GuildRoleUpdate::new("guild", 7)
+        };
+        assert_eq!(
+            serde_json::to_value(&req).unwrap(),
+            serde_json::json!({"guild_id": "guild", "role_id": 7, "hoist": 1, "permissions": 4096})
+        );
+    }
+
+    #[test]
+    fn guild_user_query_skips_unset_filters() {
+        let query = GuildUserQuery::new("guild").with_role_id(7).with_mobile_verified(false).sort_by_joined_at(SortOrder::Desc);
+        let query: Vec<_> = query.to_query().into_iter().map(|(k, v)| format!("{k}={v}")).collect();
+        assert_eq!(query, vec!["guild_id=guild", "role_id=7", "mobile_verified=0", "joined_at=1"]);
+    }
+
+    #[test]
+    fn asset_guesses_content_type() {
+        assert_eq!(Asset::path("/tmp/a.PNG").mime(), "image/png");
+        assert_eq!(Asset::path("/tmp/a.PNG").file_name, "a.PNG");
+        assert_eq!(Asset::bytes("video.mp4", vec![0]).mime(), "video/mp4");
+        assert_eq!(Asset::bytes("blob", vec![0]).mime(), "application/octet-stream");
+        assert_eq!(Asset::bytes("a.png", vec![0]).with_content_type("image/x-custom").mime(), "image/x-custom");
+    }
 }
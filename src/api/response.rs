@@ -1,9 +1,13 @@
 use serde::{Deserialize, Serialize};
-use serde_json::value::RawValue;
+use serde_json::{value::RawValue, Value};
 
 use crate::error::{KookError, KookResult};
 
-use super::objects::NotifyType;
+use super::{
+    event::Emoji,
+    permission::Permissions,
+    objects::{Attachments, ChannelType, MessageType, NotifyType, PermissionOverwrite, PermissionUser, Quote, User},
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ResponseWrap<'a, T: Deserialize<'a> = Empty> {
@@ -39,16 +43,12 @@ pub struct Page<T> {
     pub(crate) sort: PageSort,
 }
 
-impl <T> Page<T> {
-    pub(crate) const MAX_PAGE_SIZE: u32 = 1 << 16;
-}
-
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct PageMeta {
-    pub(crate) page: i32,
-    pub(crate) page_total: i32,
-    pub(crate) page_size: i32,
-    pub(crate) total: i32,
+    pub page: i32,
+    pub page_total: i32,
+    pub page_size: i32,
+    pub total: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -208,6 +208,19 @@ pub struct GuildUserListItem {
 
     #[serde(rename = "roles")]
     pub roles: Vec<u64>,
+
+    // 毫秒时间戳
+    #[serde(rename = "joined_at", default)]
+    pub joined_at: i64,
+
+    #[serde(rename = "active_time", default)]
+    pub active_time: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AssetCreate {
+    #[serde(rename = "url")]
+    pub url: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -227,3 +240,260 @@ pub struct MessageCreate {
     #[serde(rename = "nonce")]
     pub nonce: String,
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MessageList {
+    #[serde(rename = "items")]
+    pub items: Vec<ChannelMessage>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChannelMessage {
+    #[serde(rename = "id")]
+    pub id: String,
+
+    #[serde(rename = "type")]
+    pub message_type: MessageType,
+
+    #[serde(rename = "content")]
+    pub content: String,
+
+    #[serde(rename = "mention", default)]
+    pub mention: Vec<String>,
+
+    #[serde(rename = "mention_all", default)]
+    pub mention_all: bool,
+
+    #[serde(rename = "mention_roles", default)]
+    pub mention_roles: Vec<u64>,
+
+    #[serde(rename = "mention_here", default)]
+    pub mention_here: bool,
+
+    #[serde(rename = "embeds", default)]
+    pub embeds: Vec<Value>,
+
+    #[serde(rename = "attachments", default)]
+    pub attachments: Option<Attachments>,
+
+    #[serde(rename = "create_at")]
+    pub create_at: i64,
+
+    #[serde(rename = "updated_at", default)]
+    pub updated_at: i64,
+
+    #[serde(rename = "reactions", default)]
+    pub reactions: Vec<Reaction>,
+
+    #[serde(rename = "author")]
+    pub author: User,
+
+    #[serde(rename = "quote", default)]
+    pub quote: Option<Quote>,
+
+    // 只有 message/view 返回
+    #[serde(rename = "channel_id", default)]
+    pub channel_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Reaction {
+    #[serde(rename = "emoji")]
+    pub emoji: Emoji,
+
+    #[serde(rename = "count")]
+    pub count: u32,
+
+    #[serde(rename = "me")]
+    pub me: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReactionUser {
+    #[serde(rename = "id")]
+    pub id: String,
+
+    #[serde(rename = "username")]
+    pub username: String,
+
+    #[serde(rename = "nickname", default)]
+    pub nickname: String,
+
+    #[serde(rename = "identify_num")]
+    pub identify_num: String,
+
+    #[serde(rename = "online")]
+    pub online: bool,
+
+    #[serde(rename = "status")]
+    pub status: i64,
+
+    #[serde(rename = "avatar")]
+    pub avatar: String,
+
+    #[serde(rename = "bot")]
+    pub bot: bool,
+
+    #[serde(rename = "reaction_time")]
+    pub reaction_time: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserChat {
+    #[serde(rename = "code")]
+    pub code: String,
+
+    #[serde(rename = "last_read_time")]
+    pub last_read_time: i64,
+
+    #[serde(rename = "latest_msg_time")]
+    pub latest_msg_time: i64,
+
+    #[serde(rename = "unread_count")]
+    pub unread_count: i64,
+
+    #[serde(rename = "is_friend", default)]
+    pub is_friend: bool,
+
+    #[serde(rename = "is_blocked", default)]
+    pub is_blocked: bool,
+
+    #[serde(rename = "is_target_blocked", default)]
+    pub is_target_blocked: bool,
+
+    #[serde(rename = "target_info")]
+    pub target_info: UserChatTarget,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserChatTarget {
+    #[serde(rename = "id")]
+    pub id: String,
+
+    #[serde(rename = "username")]
+    pub username: String,
+
+    #[serde(rename = "online")]
+    pub online: bool,
+
+    #[serde(rename = "avatar")]
+    pub avatar: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DirectMessageList {
+    #[serde(rename = "items")]
+    pub items: Vec<DirectMessage>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DirectMessage {
+    #[serde(rename = "id")]
+    pub id: String,
+
+    #[serde(rename = "type")]
+    pub message_type: MessageType,
+
+    #[serde(rename = "content")]
+    pub content: String,
+
+    #[serde(rename = "embeds", default)]
+    pub embeds: Vec<Value>,
+
+    #[serde(rename = "attachments", default)]
+    pub attachments: Option<Attachments>,
+
+    #[serde(rename = "create_at")]
+    pub create_at: i64,
+
+    #[serde(rename = "updated_at", default)]
+    pub updated_at: i64,
+
+    #[serde(rename = "reactions", default)]
+    pub reactions: Vec<Reaction>,
+
+    #[serde(rename = "author_id")]
+    pub author_id: String,
+
+    #[serde(rename = "read_status", default)]
+    pub read_status: bool,
+
+    #[serde(rename = "quote", default)]
+    pub quote: Option<Quote>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChannelListItem {
+    #[serde(rename = "id")]
+    pub id: String,
+
+    #[serde(rename = "name")]
+    pub name: String,
+
+    #[serde(rename = "user_id")]
+    pub user_id: String,
+
+    #[serde(rename = "parent_id", default)]
+    pub parent_id: String,
+
+    #[serde(rename = "topic", default)]
+    pub topic: String,
+
+    #[serde(rename = "type")]
+    pub channel_type: ChannelType,
+
+    #[serde(rename = "level")]
+    pub level: u32,
+
+    #[serde(rename = "slow_mode", default)]
+    pub slow_mode: u32,
+
+    #[serde(rename = "has_password", default)]
+    pub has_password: bool,
+
+    #[serde(rename = "limit_amount", default)]
+    pub limit_amount: u32,
+
+    #[serde(rename = "is_category")]
+    pub is_category: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChannelRoleIndex {
+    #[serde(rename = "permission_overwrites")]
+    pub permission_overwrites: Vec<PermissionOverwrite>,
+
+    #[serde(rename = "permission_users")]
+    pub permission_users: Vec<PermissionUser>,
+
+    #[serde(rename = "permission_sync", with = "super::bool_as_u8")]
+    pub permission_sync: bool,
+}
+
+// 按角色设置时返回 role_id, 按用户设置时返回 user_id
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChannelRole {
+    #[serde(rename = "role_id", default)]
+    pub role_id: Option<u64>,
+
+    #[serde(rename = "user_id", default)]
+    pub user_id: Option<String>,
+
+    #[serde(rename = "allow")]
+    pub allow: Permissions,
+
+    #[serde(rename = "deny")]
+    pub deny: Permissions,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GuildRoleGrant {
+    #[serde(rename = "user_id")]
+    pub user_id: String,
+
+    #[serde(rename = "guild_id")]
+    pub guild_id: String,
+
+    #[serde(rename = "roles")]
+    pub roles: Vec<u64>,
+}
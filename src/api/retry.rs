@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+// 指数超过这个值时等待时间早已到达上限
+const MAX_BACKOFF_EXP: u32 = 64;
+
+// 指数退避, 第 attempt 次重试前的等待时间, attempt 从 1 开始
+// multiplier 和 jitter 为非法值时不会 panic, 结果不超过 max_delay
+pub(crate) fn backoff(initial_delay: Duration, max_delay: Duration, multiplier: f64, jitter: f64, attempt: u32) -> Duration {
+    let exp = multiplier.powi(attempt.saturating_sub(1).min(MAX_BACKOFF_EXP) as i32);
+    let delay = initial_delay.as_secs_f64() * exp;
+    let delay = if jitter > 0.0 && jitter < 1.0 {
+        delay * rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter)
+    } else {
+        delay
+    };
+    Duration::try_from_secs_f64(delay.clamp(0.0, max_delay.as_secs_f64())).unwrap_or(max_delay)
+}
+
+// 网络错误和 5xx 的重试策略, 只作用于幂等请求
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    // 第一次失败后最多重试的次数, 0 表示不重试
+    pub max_retries: u32,
+    // 第一次重试前的等待时间, 之后每次乘以 multiplier
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    // 等待时间随机浮动的比例, 0.2 表示 ±20%
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+            multiplier: 2.0,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn disabled() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    // 第 attempt 次重试前的等待时间, attempt 从 1 开始
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        backoff(self.initial_delay, self.max_delay, self.multiplier, self.jitter, attempt)
+    }
+
+    // 连接失败、超时和 5xx 视为临时错误
+    pub(crate) fn transient(err: &reqwest::Error) -> bool {
+        err.is_connect() || err.is_timeout() || err.status().is_some_and(|status| status.is_server_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_grows_until_max() {
+        let policy = RetryPolicy {
+            jitter: 0.0,
+            max_delay: Duration::from_millis(500),
+            ..Default::default()
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(200));
+        assert_eq!(policy.delay(2), Duration::from_millis(400));
+        assert_eq!(policy.delay(3), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn delay_survives_degenerate_policy() {
+        for (multiplier, jitter) in [(f64::NAN, 0.2), (f64::INFINITY, 0.2), (2.0, f64::NAN), (2.0, 5.0), (-3.0, 0.0)] {
+            let policy = RetryPolicy {
+                multiplier,
+                jitter,
+                ..Default::default()
+            };
+            for attempt in [1, 2, 100, u32::MAX] {
+                assert!(policy.delay(attempt) <= policy.max_delay);
+            }
+        }
+    }
+}
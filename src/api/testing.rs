@@ -0,0 +1,86 @@
+use std::{convert::Infallible, sync::Arc};
+
+use hyper::{
+    body::Bytes,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server,
+};
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use super::event::Event;
+use crate::{kook::BotInfo, Bot, Kook, KookError, KookHandle, Token};
+
+// 收到文本消息时把 msg_id 发送出去
+#[derive(Clone)]
+pub(crate) struct Recorder(pub(crate) mpsc::UnboundedSender<String>);
+
+impl KookHandle for Recorder {
+    type Err = KookError;
+
+    async fn on_event(&self, _kook: Arc<Kook<Self>>, event: Arc<Event>) -> Result<(), Self::Err> {
+        if let Event::Text(ref text) = *event {
+            let _ = self.0.send(text.msg_id.clone());
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn test_bot() -> Bot {
+    Bot::builder(Token::Bot("test".to_string())).build()
+}
+
+// 本地 mock http 服务, 请求体会先读完再交给 handler, 返回服务地址
+pub(crate) fn mock_server<F>(handler: F) -> String
+where
+    F: Fn(Request<Bytes>) -> Response<Body> + Send + Sync + 'static,
+{
+    let handler = Arc::new(handler);
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    listener.set_nonblocking(true).unwrap();
+    let make_service = make_service_fn(move |_| {
+        let handler = handler.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                let handler = handler.clone();
+                async move {
+                    let (parts, body) = req.into_parts();
+                    let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+                    Ok::<_, Infallible>(handler(Request::from_parts(parts, body)))
+                }
+            }))
+        }
+    });
+    tokio::spawn(Server::from_tcp(listener).unwrap().serve(make_service));
+    url
+}
+
+pub(crate) fn test_kook() -> (Arc<Kook<Recorder>>, mpsc::UnboundedReceiver<String>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let kook = Kook::with_bot(test_bot(), BotInfo { id: "bot".to_string() }, Recorder(tx));
+    (kook.to_arc(), rx)
+}
+
+pub(crate) fn text_event_body(msg_id: &str) -> Value {
+    serde_json::json!({
+        "channel_type": "GROUP",
+        "type": 1,
+        "target_id": "channel",
+        "author_id": "user",
+        "content": "hello",
+        "extra": {
+            "guild_id": "guild",
+            "channel_name": "general",
+            "mention": [],
+            "mention_all": false,
+            "mention_roles": [],
+            "mention_here": false,
+            "code": "",
+            "author": {"identify_num": "0001", "avatar": "", "username": "user", "id": "user", "nickname": "user", "roles": []}
+        },
+        "msg_id": msg_id,
+        "msg_timestamp": 0,
+        "nonce": ""
+    })
+}
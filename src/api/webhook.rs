@@ -0,0 +1,356 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hyper::{
+    body::HttpBody,
+    header::{HeaderValue, CONTENT_TYPE},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::event::Event;
+use crate::{
+    error::{KookError, KookResult},
+    kook::KookHandle,
+};
+
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    pub addr: SocketAddr,
+    pub verify_token: String,
+    // 开发者后台开启了消息加密时填写
+    pub encrypt_key: Option<String>,
+}
+
+// 请求体和解压后的最大长度, 超过的请求直接拒绝
+const MAX_BODY_SIZE: usize = 1 << 20;
+const MAX_INFLATED_SIZE: usize = 4 << 20;
+// 记住最近多少个 sn, 用于丢弃 KOOK 重试推送的重复事件
+const RECENT_SN: usize = 1024;
+
+#[derive(Deserialize)]
+struct Encrypted {
+    encrypt: String,
+}
+
+#[derive(Deserialize)]
+struct Payload {
+    s: u8,
+    #[serde(default)]
+    sn: Option<u64>,
+    d: Value,
+}
+
+struct WebhookState {
+    config: WebhookConfig,
+    recent: Mutex<RecentSn>,
+}
+
+#[derive(Default)]
+struct RecentSn {
+    seen: HashSet<u64>,
+    order: VecDeque<u64>,
+}
+
+impl RecentSn {
+    // 第一次见到该 sn 时返回 true
+    fn insert(&mut self, sn: u64) -> bool {
+        if !self.seen.insert(sn) {
+            return false;
+        }
+        self.order.push_back(sn);
+        if self.order.len() > RECENT_SN {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+impl<H: KookHandle> crate::Kook<H> {
+    pub async fn webhook_loop(self: Arc<Self>, config: WebhookConfig) -> KookResult<()> {
+        let listener = std::net::TcpListener::bind(config.addr).map_err(|err| KookError::Webhook(format!("bind {} failed: {}", config.addr, err)))?;
+        self.serve_webhook(listener, config).await
+    }
+
+    async fn serve_webhook(self: Arc<Self>, listener: std::net::TcpListener, config: WebhookConfig) -> KookResult<()> {
+        listener
+            .set_nonblocking(true)
+            .map_err(|err| KookError::Webhook(format!("set nonblocking failed: {}", err)))?;
+        let state = Arc::new(WebhookState {
+            config,
+            recent: Mutex::default(),
+        });
+        let kook = self.clone();
+        let make_service = make_service_fn(move |_| {
+            let kook = kook.clone();
+            let state = state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let kook = kook.clone();
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(kook.webhook_response(&state, req).await) }
+                }))
+            }
+        });
+        Server::from_tcp(listener)?
+            .serve(make_service)
+            .with_graceful_shutdown(self.shutdown.clone().cancelled_owned())
+            .await?;
+        self.finish().await;
+        Ok(())
+    }
+
+    async fn webhook_response(self: &Arc<Self>, state: &WebhookState, req: Request<Body>) -> Response<Body> {
+        if req.method() != Method::POST {
+            return Self::reply(StatusCode::METHOD_NOT_ALLOWED, Body::empty());
+        }
+        let body = match Self::read_body(req.into_body()).await {
+            Ok(body) => body,
+            Err(err) => {
+                tracing::error!("read webhook body failed: {}", err);
+                let status = match err {
+                    KookError::Hyper(_) => StatusCode::BAD_REQUEST,
+                    _ => StatusCode::PAYLOAD_TOO_LARGE,
+                };
+                return Self::reply(status, Body::empty());
+            }
+        };
+        match self.handle_webhook(state, &body) {
+            Ok(Some(challenge)) => Self::reply(StatusCode::OK, Body::from(serde_json::json!({ "challenge": challenge }).to_string())),
+            Ok(None) => Self::reply(StatusCode::OK, Body::empty()),
+            Err(err) => {
+                tracing::error!("handle webhook failed: {}", err);
+                Self::reply(StatusCode::BAD_REQUEST, Body::empty())
+            }
+        }
+    }
+
+    async fn read_body(mut body: Body) -> KookResult<Vec<u8>> {
+        if body.size_hint().lower() > MAX_BODY_SIZE as u64 {
+            return Err(KookError::Webhook(format!("body larger than {} bytes", MAX_BODY_SIZE)));
+        }
+        let mut ret = Vec::new();
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk?;
+            if ret.len() + chunk.len() > MAX_BODY_SIZE {
+                return Err(KookError::Webhook(format!("body larger than {} bytes", MAX_BODY_SIZE)));
+            }
+            ret.extend_from_slice(&chunk);
+        }
+        Ok(ret)
+    }
+
+    fn reply(status: StatusCode, body: Body) -> Response<Body> {
+        let mut resp = Response::new(body);
+        *resp.status_mut() = status;
+        resp.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        resp
+    }
+
+    // 返回需要回应的 challenge
+    fn handle_webhook(self: &Arc<Self>, state: &WebhookState, body: &[u8]) -> KookResult<Option<String>> {
+        let config = &state.config;
+        let body = match body.iter().find(|x| !x.is_ascii_whitespace()) {
+            Some(b'{') => String::from_utf8_lossy(body).into_owned(),
+            _ => {
+                let data = miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(body, MAX_INFLATED_SIZE)?;
+                String::from_utf8(data).map_err(|err| KookError::Decompress(format!("decompressed body is not utf8: {}", err)))?
+            }
+        };
+        tracing::debug!("recv webhook:{}", body);
+        let body = match (serde_json::from_str::<Encrypted>(&body), config.encrypt_key.as_deref()) {
+            (Ok(encrypted), Some(encrypt_key)) => Self::decrypt(encrypt_key, &encrypted.encrypt)?,
+            (Ok(_), None) => return Err(KookError::Webhook("encrypted body without encrypt_key".to_string())),
+            (Err(_), Some(_)) => return Err(KookError::Webhook("plaintext body while encrypt_key is set".to_string())),
+            (Err(_), None) => body,
+        };
+        let Payload { s, sn, d } = serde_json::from_str(&body)?;
+        if s != 0 {
+            return Err(KookError::Webhook(format!("unexpected signal {}", s)));
+        }
+        if d.get("verify_token").and_then(Value::as_str) != Some(config.verify_token.as_str()) {
+            return Err(KookError::Webhook("verify token mismatch".to_string()));
+        }
+        if d.get("channel_type").and_then(Value::as_str) == Some("WEBHOOK_CHALLENGE") {
+            let challenge = d.get("challenge").and_then(Value::as_str).unwrap_or_default();
+            return Ok(Some(challenge.to_string()));
+        }
+        if let Some(sn) = sn {
+            if !state.recent.lock().unwrap().insert(sn) {
+                tracing::debug!("drop duplicate webhook sn: {}", sn);
+                return Ok(None);
+            }
+        }
+        match serde_json::from_value::<Event>(d.clone()) {
+            Ok(event) => self.dispatch(event),
+            Err(_) => self.handle.on_unknown_event(&d),
+        }
+        Ok(None)
+    }
+
+    // encrypt 为 base64(iv + base64(aes-256-cbc 密文)), 密钥不足 32 位时补 \0
+    fn decrypt(encrypt_key: &str, encrypt: &str) -> KookResult<String> {
+        let data = BASE64
+            .decode(encrypt)
+            .map_err(|err| KookError::Webhook(format!("decode encrypt failed: {}", err)))?;
+        if data.len() < 16 {
+            return Err(KookError::Webhook("encrypt too short".to_string()));
+        }
+        let (iv, data) = data.split_at(16);
+        let data = BASE64
+            .decode(data)
+            .map_err(|err| KookError::Webhook(format!("decode encrypt failed: {}", err)))?;
+        let mut key = [0u8; 32];
+        let len = encrypt_key.len().min(key.len());
+        key[..len].copy_from_slice(&encrypt_key.as_bytes()[..len]);
+        let data = cbc::Decryptor::<aes::Aes256>::new(&key.into(), iv.into())
+            .decrypt_padded_vec_mut::<Pkcs7>(&data)
+            .map_err(|err| KookError::Webhook(format!("decrypt failed: {}", err)))?;
+        String::from_utf8(data).map_err(|err| KookError::Webhook(format!("decrypted body is not utf8: {}", err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::testing::{test_kook, text_event_body};
+    use aes::cipher::BlockEncryptMut;
+
+    const VERIFY_TOKEN: &str = "verify";
+    const ENCRYPT_KEY: &str = "encrypt";
+
+    async fn start(encrypt_key: Option<&str>) -> (String, tokio::sync::mpsc::UnboundedReceiver<String>) {
+        let (kook, rx) = test_kook();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/webhook", listener.local_addr().unwrap());
+        let config = WebhookConfig {
+            addr: listener.local_addr().unwrap(),
+            verify_token: VERIFY_TOKEN.to_string(),
+            encrypt_key: encrypt_key.map(ToString::to_string),
+        };
+        tokio::spawn(kook.serve_webhook(listener, config));
+        (url, rx)
+    }
+
+    fn encrypt(body: &str) -> String {
+        let mut key = [0u8; 32];
+        key[..ENCRYPT_KEY.len()].copy_from_slice(ENCRYPT_KEY.as_bytes());
+        let iv = *b"0123456789abcdef";
+        let data = cbc::Encryptor::<aes::Aes256>::new(&key.into(), &iv.into()).encrypt_padded_vec_mut::<Pkcs7>(body.as_bytes());
+        let mut ret = iv.to_vec();
+        ret.extend(BASE64.encode(data).into_bytes());
+        BASE64.encode(ret)
+    }
+
+    fn event(msg_id: &str, verify_token: &str) -> String {
+        event_sn(1, msg_id, verify_token)
+    }
+
+    fn event_sn(sn: u64, msg_id: &str, verify_token: &str) -> String {
+        let mut d = text_event_body(msg_id);
+        d["verify_token"] = verify_token.into();
+        serde_json::json!({"s": 0, "sn": sn, "d": d}).to_string()
+    }
+
+    async fn recv_event(rx: &mut tokio::sync::mpsc::UnboundedReceiver<String>) -> String {
+        tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv()).await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn answers_challenge() {
+        let (url, _rx) = start(None).await;
+        let body = serde_json::json!({
+            "s": 0,
+            "d": {"type": 255, "channel_type": "WEBHOOK_CHALLENGE", "challenge": "abc", "verify_token": VERIFY_TOKEN}
+        });
+        let resp = reqwest::Client::new().post(url).body(body.to_string()).send().await.unwrap();
+        assert!(resp.status().is_success());
+        assert_eq!(resp.json::<Value>().await.unwrap(), serde_json::json!({"challenge": "abc"}));
+    }
+
+    #[tokio::test]
+    async fn dispatches_plain_event() {
+        let (url, mut rx) = start(None).await;
+        let resp = reqwest::Client::new().post(url).body(event("m1", VERIFY_TOKEN)).send().await.unwrap();
+        assert!(resp.status().is_success());
+        assert_eq!(recv_event(&mut rx).await, "m1");
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_verify_token() {
+        let (url, mut rx) = start(None).await;
+        let resp = reqwest::Client::new().post(url).body(event("m1", "wrong")).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn dispatches_compressed_encrypted_event() {
+        let (url, mut rx) = start(Some(ENCRYPT_KEY)).await;
+        let body = serde_json::json!({ "encrypt": encrypt(&event("m2", VERIFY_TOKEN)) }).to_string();
+        let body = miniz_oxide::deflate::compress_to_vec_zlib(body.as_bytes(), 6);
+        let resp = reqwest::Client::new().post(url).body(body).send().await.unwrap();
+        assert!(resp.status().is_success());
+        assert_eq!(recv_event(&mut rx).await, "m2");
+    }
+
+    #[tokio::test]
+    async fn rejects_oversized_body() {
+        let (url, mut rx) = start(None).await;
+        let resp = reqwest::Client::new().post(url).body(vec![b' '; MAX_BODY_SIZE + 1]).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_zlib_bomb() {
+        let (url, mut rx) = start(None).await;
+        let body = miniz_oxide::deflate::compress_to_vec_zlib(&vec![b' '; MAX_INFLATED_SIZE * 2], 6);
+        assert!(body.len() < MAX_BODY_SIZE);
+        let resp = reqwest::Client::new().post(url).body(body).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_plaintext_when_encrypted() {
+        let (url, mut rx) = start(Some(ENCRYPT_KEY)).await;
+        let resp = reqwest::Client::new().post(url).body(event("m1", VERIFY_TOKEN)).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn drops_duplicate_sn() {
+        let (url, mut rx) = start(None).await;
+        let client = reqwest::Client::new();
+        for (sn, msg_id) in [(1, "m1"), (1, "m1"), (2, "m2")] {
+            let resp = client.post(&url).body(event_sn(sn, msg_id, VERIFY_TOKEN)).send().await.unwrap();
+            assert!(resp.status().is_success());
+        }
+        assert_eq!(recv_event(&mut rx).await, "m1");
+        assert_eq!(recv_event(&mut rx).await, "m2");
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn decrypt_needs_matching_key() {
+        let encrypted = encrypt(&event("m3", VERIFY_TOKEN));
+        assert_eq!(
+            crate::Kook::<crate::api::testing::Recorder>::decrypt(ENCRYPT_KEY, &encrypted).unwrap(),
+            event("m3", VERIFY_TOKEN)
+        );
+        assert!(crate::Kook::<crate::api::testing::Recorder>::decrypt("other", &encrypted).is_err());
+    }
+}
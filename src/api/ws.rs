@@ -1,9 +1,9 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
 
-use super::event::Event;
+use super::{event::Event, retry};
 use crate::{
     error::{KookError, KookResult},
-    kook::KookHandle,
+    kook::{DisconnectReason, KookHandle},
 };
 use futures_util::{SinkExt, StreamExt};
 use serde::{
@@ -12,138 +12,439 @@ use serde::{
     Deserialize, Serialize,
 };
 use serde_json::Value;
-use tokio::net::TcpStream;
+use tokio::{net::TcpStream, time::Instant};
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 
 type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
+// resume 失败, 需要重新建立会话: 40106 缺少参数, 40107 session 过期, 40108 无效的 sn
+const RESUME_FAILED_CODES: [i32; 3] = [40106, 40107, 40108];
+
+#[derive(Debug, Clone)]
+pub struct SequenceConfig {
+    // 最多缓存多少个跳号之后到达的事件, 超过后放弃等待缺失的 sn
+    pub max_gap: usize,
+    // 缺失的 sn 最多等待多久
+    pub gap_timeout: Duration,
+}
+
+impl Default for SequenceConfig {
+    fn default() -> Self {
+        Self {
+            max_gap: 128,
+            gap_timeout: Duration::from_secs(6),
+        }
+    }
+}
+
+// 按 sn 顺序交付事件, 丢弃已经交付过的 sn, 缓存跳号之后的事件
+#[derive(Debug)]
+struct Sequencer<T> {
+    config: SequenceConfig,
+    last_sn: u64,
+    pending: BTreeMap<u64, T>,
+    gap_since: Option<Instant>,
+}
+
+impl<T> Sequencer<T> {
+    fn new(config: SequenceConfig) -> Self {
+        Self {
+            config,
+            last_sn: 0,
+            pending: BTreeMap::new(),
+            gap_since: None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.last_sn = 0;
+        self.pending.clear();
+        self.gap_since = None;
+    }
+
+    fn push(&mut self, sn: u64, item: T) -> Vec<T> {
+        if sn <= self.last_sn || self.pending.contains_key(&sn) {
+            tracing::debug!("drop duplicate sn: {}", sn);
+            return Vec::new();
+        }
+        self.pending.insert(sn, item);
+        let mut ret = self.drain_ready();
+        if !ret.is_empty() {
+            self.gap_since = None;
+        }
+        if self.pending.len() > self.config.max_gap {
+            tracing::warn!("sn gap after {} exceeds {} events, skip it", self.last_sn, self.config.max_gap);
+            ret.extend(self.drain_all());
+        }
+        self.update_gap();
+        ret
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.gap_since.map(|x| x + self.config.gap_timeout)
+    }
+
+    fn flush_expired(&mut self, now: Instant) -> Vec<T> {
+        match self.deadline() {
+            Some(deadline) if deadline <= now => {
+                tracing::warn!("sn gap after {} timeout, skip it", self.last_sn);
+                let ret = self.drain_all();
+                self.update_gap();
+                ret
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn drain_ready(&mut self) -> Vec<T> {
+        let mut ret = Vec::new();
+        while let Some(item) = self.pending.remove(&(self.last_sn + 1)) {
+            self.last_sn += 1;
+            ret.push(item);
+        }
+        ret
+    }
+
+    fn drain_all(&mut self) -> Vec<T> {
+        let pending = std::mem::take(&mut self.pending);
+        if let Some(&sn) = pending.keys().next_back() {
+            self.last_sn = sn;
+        }
+        pending.into_values().collect()
+    }
+
+    fn update_gap(&mut self) {
+        if self.pending.is_empty() {
+            self.gap_since = None;
+        } else if self.gap_since.is_none() {
+            self.gap_since = Some(Instant::now());
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ReconnectPolicy {
+    // 第一次重连前的等待时间, 之后每次乘以 multiplier
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    // 等待时间随机浮动的比例, 0.2 表示 ±20%
+    pub jitter: f64,
+    // 连续失败多少次后放弃, None 表示一直重连
+    pub max_attempts: Option<u32>,
+    pub on_give_up: Option<Arc<dyn Fn(u32) + Send + Sync>>,
+    // 每隔 ping_interval 检查一次心跳, 连续 ping_count 次没有收到 pong 时发送 ping, 再过一次仍没有 pong 视为超时
+    pub ping_interval: Duration,
+    pub ping_count: u32,
+    // 连接建立后等待 hello 的时间
+    pub hello_timeout: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_attempts: None,
+            on_give_up: None,
+            ping_interval: Duration::from_secs(6),
+            ping_count: 4,
+            hello_timeout: Duration::from_secs(6),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    // multiplier 至少为 1, jitter 在 [0, 1) 之间, 非法值回退到默认值
+    pub(crate) fn normalized(mut self) -> Self {
+        let default = Self::default();
+        self.multiplier = match self.multiplier {
+            x if x.is_finite() => x.max(1.0),
+            _ => default.multiplier,
+        };
+        self.jitter = match self.jitter {
+            x if x.is_finite() => x.clamp(0.0, 0.99),
+            _ => 0.0,
+        };
+        self
+    }
+
+    // 第 attempt 次重连前的等待时间, attempt 从 1 开始
+    fn delay(&self, attempt: u32) -> Duration {
+        retry::backoff(self.initial_delay, self.max_delay, self.multiplier, self.jitter, attempt)
+    }
+
+    fn give_up(&self, attempt: u32) -> bool {
+        self.max_attempts.is_some_and(|max| attempt > max)
+    }
+}
+
+#[derive(Debug)]
+struct WsSession {
+    session_id: Option<String>,
+    sequencer: Sequencer<Message>,
+    // 连续重连失败的次数
+    attempts: u32,
+}
+
+impl WsSession {
+    fn new(config: SequenceConfig) -> Self {
+        Self {
+            session_id: None,
+            sequencer: Sequencer::new(config),
+            attempts: 0,
+        }
+    }
+
+    fn max_sn(&self) -> u64 {
+        self.sequencer.last_sn
+    }
+
+    fn connect_url(&self, url: &str) -> String {
+        match self.session_id {
+            Some(ref session_id) => {
+                let sep = if url.contains('?') { '&' } else { '?' };
+                format!("{url}{sep}resume=1&sn={}&session_id={session_id}", self.max_sn())
+            }
+            None => url.to_string(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.session_id = None;
+        self.sequencer.reset();
+    }
+
+    fn check_code(&mut self, code: i32) {
+        if RESUME_FAILED_CODES.contains(&code) {
+            tracing::warn!("resume failed code: {}, start new session", code);
+            self.reset();
+        }
+    }
+}
+
 impl<H: KookHandle> crate::Kook<H> {
     pub async fn event_loop(self: Arc<Self>) -> KookResult<()> {
-        let mut state = WsStateMachine::GetGateway;
-        let mut max_sn: u64 = 0;
-        loop {
-            match state {
-                WsStateMachine::GetGateway => match self.bot.gateway_index(false).await {
-                    Ok(url) => state = WsStateMachine::ConnectGateway(url),
-                    Err(err) => tracing::error!("get ws url failed: {}", err),
-                },
-                WsStateMachine::ConnectGateway(url) => match tokio_tungstenite::connect_async(url.as_str()).await {
-                    Ok((ws_stream, _)) => state = WsStateMachine::WaitHello(ws_stream, std::time::SystemTime::now()),
-                    Err(err) => {
-                        tracing::error!("connect ws url failed: {}", err);
-                        state = WsStateMachine::GetGateway;
+        let session = WsSession::new(self.sequence.clone());
+        self.run(WsStateMachine::GetGateway, session).await
+    }
+
+    async fn run(self: Arc<Self>, mut state: WsStateMachine, mut session: WsSession) -> KookResult<()> {
+        while !self.shutdown.is_cancelled() {
+            state = match self.next_state(state, &mut session).await {
+                WsStateMachine::GiveUp => {
+                    tracing::error!("reconnect gave up after {} attempts", session.attempts);
+                    if let Some(ref on_give_up) = self.reconnect.on_give_up {
+                        on_give_up(session.attempts);
                     }
-                },
-                WsStateMachine::WaitHello(mut ws_stream, wait_start) => match Self::next_message(&mut ws_stream).await {
-                    Ok(Message::Hello { code, session_id: _ }) => {
-                        if code == 0 {
-                            state = WsStateMachine::Ping(ws_stream)
-                        } else {
-                            tracing::error!("wait hello failed err code: {}", code);
-                            state = WsStateMachine::GetGateway;
-                        }
+                    self.finish().await;
+                    return Err(KookError::Reconnect { attempts: session.attempts });
+                }
+                state => state,
+            };
+        }
+        state.close().await;
+        self.finish().await;
+        Ok(())
+    }
+
+    async fn next_state(self: &Arc<Self>, state: WsStateMachine, session: &mut WsSession) -> WsStateMachine {
+        match state {
+            WsStateMachine::GetGateway => {
+                if session.attempts > 0 {
+                    if self.reconnect.give_up(session.attempts) {
+                        return WsStateMachine::GiveUp;
                     }
-                    Ok(Message::Reconnect { code, err }) => {
-                        tracing::error!("reconnect code: {} err: {}", code, err);
-                        state = WsStateMachine::GetGateway;
+                    let delay = self.reconnect.delay(session.attempts);
+                    tracing::warn!("reconnect attempt {} in {:?}", session.attempts, delay);
+                    if self.until_shutdown(tokio::time::sleep(delay)).await.is_none() {
+                        return WsStateMachine::GetGateway;
                     }
-                    Ok(_) => {
-                        state = if wait_start.elapsed().map(|x| x.as_secs()).unwrap_or(200) > 6 {
-                            WsStateMachine::GetGateway
-                        } else {
-                            WsStateMachine::WaitHello(ws_stream, wait_start)
-                        };
+                }
+                session.attempts += 1;
+                match self.until_shutdown(self.bot.gateway_index(self.compress)).await {
+                    Some(Ok(url)) => WsStateMachine::ConnectGateway(session.connect_url(&url)),
+                    Some(Err(err)) => {
+                        tracing::error!("get ws url failed: {}", err);
+                        WsStateMachine::GetGateway
                     }
-                    Err(err) => {
-                        tracing::error!("wait hello failed: {}", err);
-                        state = WsStateMachine::GetGateway;
+                    None => WsStateMachine::GetGateway,
+                }
+            }
+            WsStateMachine::ConnectGateway(url) => match self.until_shutdown(tokio_tungstenite::connect_async(url.as_str())).await {
+                Some(Ok((ws_stream, _))) => WsStateMachine::WaitHello(ws_stream, Instant::now() + self.reconnect.hello_timeout),
+                Some(Err(err)) => {
+                    tracing::error!("connect ws url failed: {}", err);
+                    WsStateMachine::GetGateway
+                }
+                None => WsStateMachine::GetGateway,
+            },
+            WsStateMachine::WaitHello(mut ws_stream, deadline) => {
+                let hello = tokio::time::timeout_at(deadline, Self::next_message(&mut ws_stream));
+                match self.until_shutdown(hello).await {
+                    None => WsStateMachine::WaitHello(ws_stream, deadline),
+                    Some(Err(_)) => {
+                        tracing::error!("wait hello timeout");
+                        WsStateMachine::GetGateway
                     }
-                },
-                WsStateMachine::Ping(mut ws_stream) => {
-                    let mut interval = tokio::time::interval(std::time::Duration::from_secs(6));
-                    let mut ping_count = 0;
-                    loop {
-                        tokio::select! {
-                            _ = interval.tick() => {
-                                match ping_count {
-                                    0..=3 => {},
-                                    4 => {
-                                        match Self::ping(&mut ws_stream, max_sn).await {
-                                            Ok(_) => {
-                                                tracing::debug!("ping success");
-                                            },
-                                            Err(err) => {
-                                                tracing::error!("ping failed: {}", err);
-                                                state = WsStateMachine::GetGateway;
-                                                break;
-                                            },
-                                        }
-                                    },
-                                    _ => {
-                                        tracing::error!("ping timeout");
-                                        state = WsStateMachine::GetGateway;
-                                        break;
+                    Some(Ok(msg)) => match msg {
+                        Ok(Message::Hello { code: 0, session_id }) => {
+                            session.attempts = 0;
+                            if session.session_id.is_some() {
+                                WsStateMachine::Resume(ws_stream)
+                            } else {
+                                session.session_id = session_id;
+                                self.handle.on_ready(session.session_id.as_deref().unwrap_or_default());
+                                WsStateMachine::Ping(ws_stream)
+                            }
+                        }
+                        Ok(Message::Hello { code, session_id: _ }) => {
+                            tracing::error!("wait hello failed err code: {}", code);
+                            session.check_code(code);
+                            WsStateMachine::GetGateway
+                        }
+                        Ok(Message::Reconnect { code, err }) => {
+                            tracing::error!("reconnect code: {} err: {}", code, err);
+                            session.check_code(code);
+                            self.handle.on_disconnect(&DisconnectReason::Reconnect { code, err: err.clone() });
+                            self.handle.on_reconnect(code, &err);
+                            WsStateMachine::GetGateway
+                        }
+                        Ok(_) => WsStateMachine::WaitHello(ws_stream, deadline),
+                        Err(err) => {
+                            tracing::error!("wait hello failed: {}", err);
+                            WsStateMachine::GetGateway
+                        }
+                    },
+                }
+            }
+            WsStateMachine::Resume(mut ws_stream) => match Self::send_message(&mut ws_stream, &Message::Resume { sn: session.max_sn() }).await {
+                Ok(_) => {
+                    tracing::debug!("resume sent sn: {}", session.max_sn());
+                    WsStateMachine::Ping(ws_stream)
+                }
+                Err(err) => {
+                    tracing::error!("resume failed: {}", err);
+                    WsStateMachine::GetGateway
+                }
+            },
+            WsStateMachine::GiveUp => WsStateMachine::GiveUp,
+            WsStateMachine::Ping(mut ws_stream) => {
+                let mut interval = tokio::time::interval(self.reconnect.ping_interval);
+                let mut ping_count = 0;
+                loop {
+                    tokio::select! {
+                        _ = self.shutdown.cancelled() => {
+                            self.handle.on_disconnect(&DisconnectReason::Shutdown);
+                            break WsStateMachine::Ping(ws_stream);
+                        }
+                        _ = interval.tick() => {
+                            match ping_count.cmp(&self.reconnect.ping_count) {
+                                std::cmp::Ordering::Less => {},
+                                std::cmp::Ordering::Equal => {
+                                    match Self::send_message(&mut ws_stream, &Message::Ping { sn: session.max_sn() }).await {
+                                        Ok(_) => {
+                                            tracing::debug!("ping success");
+                                        },
+                                        Err(err) => {
+                                            tracing::error!("ping failed: {}", err);
+                                            self.handle.on_disconnect(&DisconnectReason::Error(err.to_string()));
+                                            break WsStateMachine::GetGateway;
+                                        },
                                     }
+                                },
+                                _ => {
+                                    tracing::error!("ping timeout");
+                                    self.handle.on_disconnect(&DisconnectReason::PingTimeout);
+                                    break WsStateMachine::GetGateway;
                                 }
-                                ping_count += 1;
                             }
-                            msg = Self::next_message(&mut ws_stream) => {
-                                match msg {
-                                    Ok(Message::Reconnect { code, err }) => {
-                                        tracing::error!("reconnect code: {} err: {}", code, err);
-                                        state = WsStateMachine::GetGateway;
-                                        break;
-                                    },
-                                    Ok(Message::Event {sn, event}) => {
-                                        max_sn = max_sn.max(sn);
-                                        if self.handle.skip_self() && event.author_id() == self.bot_info.id {
-                                            continue
-                                        }
-                                        let kook = self.clone();
-                                        let event = event.to_arc();
-                                        let handle = self.handle.clone();
-                                        tokio::spawn(async move {
-                                            match handle.on_event(kook, event).await {
-                                                Ok(_) => {},
-                                                Err(err) => handle.error_handle(&err),
-                                            }
-                                        });
-                                    },
-                                    Ok(Message::UnknownEvent {sn, event}) => {
-                                        max_sn = max_sn.max(sn);
-                                        tracing::error!("unknown event:{}", serde_json::to_string(&event).unwrap_or("to string failed".to_string()));
-                                    },
-                                    Ok(Message::Pong) => {
-                                        ping_count = 0;
-                                    },
-                                    Ok(_) => {},
-                                    Err(err) => {
-                                        tracing::error!("recv message failed err:{}", err);
-                                    },
-                                }
+                            ping_count += 1;
+                        }
+                        _ = Self::gap_timeout(session.sequencer.deadline()) => {
+                            self.deliver(session.sequencer.flush_expired(Instant::now()));
+                        }
+                        msg = Self::next_message(&mut ws_stream) => {
+                            match msg {
+                                Ok(Message::Reconnect { code, err }) => {
+                                    tracing::error!("reconnect code: {} err: {}", code, err);
+                                    session.check_code(code);
+                                    self.handle.on_disconnect(&DisconnectReason::Reconnect { code, err: err.clone() });
+                                    self.handle.on_reconnect(code, &err);
+                                    break WsStateMachine::GetGateway;
+                                },
+                                Ok(msg @ (Message::Event { sn, .. } | Message::UnknownEvent { sn, .. })) => {
+                                    self.deliver(session.sequencer.push(sn, msg));
+                                },
+                                Ok(Message::ResumeAck { session_id }) => {
+                                    tracing::debug!("resume ack session_id: {}", session_id);
+                                    self.handle.on_resume(&session_id);
+                                    session.session_id = Some(session_id);
+                                },
+                                Ok(Message::Pong) => {
+                                    ping_count = 0;
+                                },
+                                Ok(_) => {},
+                                Err(KookError::Websocket(err)) => {
+                                    tracing::error!("ws disconnected err:{}", err);
+                                    self.handle.on_disconnect(&DisconnectReason::Error(err.to_string()));
+                                    break WsStateMachine::GetGateway;
+                                },
+                                Err(err) => {
+                                    tracing::error!("recv message failed err:{}", err);
+                                },
                             }
                         }
                     }
-                } // WsStateMachine::Resume(_) => {} TODO
+                }
             }
         }
     }
 
+    // 按 sn 顺序交给 dispatch, 处理完成的顺序由 DispatchStrategy 决定:
+    // Bounded/Concurrent 并发执行不保证顺序, Sequential 严格按 sn 顺序, PerTarget/PerGuild 在同一队列内有序
+    fn deliver(self: &Arc<Self>, messages: Vec<Message>) {
+        for msg in messages {
+            match msg {
+                Message::Event { sn: _, event } => self.dispatch(event),
+                Message::UnknownEvent { sn: _, event } => self.handle.on_unknown_event(&event),
+                _ => {}
+            }
+        }
+    }
+
+    async fn gap_timeout(deadline: Option<Instant>) {
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    }
+
     async fn next_message(ws_stream: &mut WsStream) -> KookResult<Message> {
         let msg = ws_stream.next().await;
         let Some(msg) = msg else {
-            return Err(KookError::Custom("unreachable".to_string()));
+            return Err(tokio_tungstenite::tungstenite::Error::ConnectionClosed.into());
         };
-        let tokio_tungstenite::tungstenite::Message::Text(msg) = msg? else {
-            return Err(KookError::Custom("ws message not match".to_string()));
+        let msg = match msg? {
+            tokio_tungstenite::tungstenite::Message::Text(msg) => msg,
+            tokio_tungstenite::tungstenite::Message::Binary(data) => Self::inflate(&data)?,
+            _ => return Err(KookError::Custom("ws message not match".to_string())),
         };
         tracing::debug!("recv message:{}", msg);
         let ret = serde_json::from_str(&msg)?;
         Ok(ret)
     }
 
-    async fn ping(ws_stream: &mut WsStream, sn: u64) -> KookResult<()> {
-        let body = serde_json::to_string(&Message::Ping { sn })?;
+    // compress=1 时服务端下发 zlib 压缩的二进制帧
+    fn inflate(data: &[u8]) -> KookResult<String> {
+        let data = miniz_oxide::inflate::decompress_to_vec_zlib(data)?;
+        String::from_utf8(data).map_err(|err| KookError::Decompress(format!("decompressed message is not utf8: {err}")))
+    }
+
+    async fn send_message(ws_stream: &mut WsStream, msg: &Message) -> KookResult<()> {
+        let body = serde_json::to_string(msg)?;
         let body = tokio_tungstenite::tungstenite::Message::Text(body);
         ws_stream.send(body).await?;
         Ok(())
@@ -154,12 +455,24 @@ impl<H: KookHandle> crate::Kook<H> {
 enum WsStateMachine {
     GetGateway,
     ConnectGateway(String),
-    WaitHello(WsStream, std::time::SystemTime),
+    WaitHello(WsStream, Instant),
     Ping(WsStream),
-    // Resume(WsStream), TODO
+    Resume(WsStream),
+    GiveUp,
+}
+
+impl WsStateMachine {
+    async fn close(self) {
+        if let WsStateMachine::WaitHello(mut ws_stream, _) | WsStateMachine::Ping(mut ws_stream) | WsStateMachine::Resume(mut ws_stream) = self {
+            if let Err(err) = ws_stream.close(None).await {
+                tracing::debug!("close ws failed: {}", err);
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 enum Message {
     Event { sn: u64, event: Event },
     UnknownEvent { sn: u64, event: Value },
@@ -225,17 +538,18 @@ impl<'de> Deserialize<'de> for Message {
             {
                 #[derive(Deserialize)]
                 #[serde(untagged)]
+                #[allow(clippy::large_enum_variant)]
                 enum Data {
                     Event(Event),
+                    Reconnect {
+                        code: i32,
+                        err: String,
+                    },
                     Hello {
                         code: i32,
                         #[serde(default)]
                         session_id: Option<String>,
                     },
-                    Reconnect {
-                        code: i32,
-                        err: String,
-                    },
                     ResumeAck {
                         session_id: String,
                     },
@@ -359,3 +673,517 @@ impl Serialize for Message {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::testing::{test_bot, test_kook, text_event_body, Recorder},
+        dispatch::{DispatchConfig, DispatchStrategy},
+        kook::BotInfo,
+        Kook,
+    };
+    use tokio::{net::TcpListener, sync::mpsc};
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    fn text_event(sn: u64, msg_id: &str) -> WsMessage {
+        frame(serde_json::json!({"s": 0, "sn": sn, "d": text_event_body(msg_id)}))
+    }
+
+    fn frame(body: Value) -> WsMessage {
+        WsMessage::Text(body.to_string())
+    }
+
+    // 启动一个只接受一次连接的本地网关, 返回网关地址和握手时的请求 uri
+    // 握手回调的错误类型由 tungstenite 决定
+    #[allow(clippy::result_large_err)]
+    async fn stand_in<F, Fut>(f: F) -> (String, tokio::task::JoinHandle<String>)
+    where
+        F: FnOnce(WebSocketStream<TcpStream>) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/gateway?compress=0", listener.local_addr().unwrap());
+        let task = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut uri = String::new();
+            let ws = tokio_tungstenite::accept_hdr_async(stream, |req: &tokio_tungstenite::tungstenite::handshake::server::Request, resp| {
+                uri = req.uri().to_string();
+                Ok(resp)
+            })
+            .await
+            .unwrap();
+            f(ws).await;
+            uri
+        });
+        (url, task)
+    }
+
+    fn resumable_session(sn: u64) -> WsSession {
+        let mut session = WsSession::new(SequenceConfig::default());
+        session.session_id = Some("session".to_string());
+        session.sequencer.last_sn = sn;
+        session
+    }
+
+    async fn run_until_disconnect<H: KookHandle>(kook: &Arc<Kook<H>>, url: String, session: &mut WsSession) {
+        let mut state = WsStateMachine::ConnectGateway(session.connect_url(&url));
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                state = kook.next_state(state, session).await;
+                if matches!(state, WsStateMachine::GetGateway) {
+                    break;
+                }
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    async fn recv_event(rx: &mut mpsc::UnboundedReceiver<String>) -> String {
+        tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv()).await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn hello_keeps_session_for_resume() {
+        let (kook, mut rx) = test_kook();
+        let (url, server) = stand_in(|mut ws| async move {
+            ws.send(frame(serde_json::json!({"s": 1, "d": {"code": 0, "session_id": "session"}}))).await.unwrap();
+            ws.send(text_event(1, "m1")).await.unwrap();
+            ws.send(text_event(2, "m2")).await.unwrap();
+            ws.close(None).await.unwrap();
+        })
+        .await;
+        let mut session = WsSession::new(SequenceConfig::default());
+        run_until_disconnect(&kook, url.clone(), &mut session).await;
+        assert_eq!(server.await.unwrap(), "/gateway?compress=0");
+
+        assert_eq!(session.session_id.as_deref(), Some("session"));
+        assert_eq!(session.max_sn(), 2);
+        assert_eq!(recv_event(&mut rx).await, "m1");
+        assert_eq!(recv_event(&mut rx).await, "m2");
+        assert_eq!(session.connect_url(&url), format!("{url}&resume=1&sn=2&session_id=session"));
+    }
+
+    #[tokio::test]
+    async fn resume_sends_sn_and_handles_ack() {
+        let (kook, mut rx) = test_kook();
+        let (url, server) = stand_in(|mut ws| async move {
+            ws.send(frame(serde_json::json!({"s": 1, "d": {"code": 0, "session_id": "session"}}))).await.unwrap();
+            let resume: Value = serde_json::from_str(ws.next().await.unwrap().unwrap().to_text().unwrap()).unwrap();
+            assert_eq!(resume, serde_json::json!({"s": 4, "sn": 2}));
+            ws.send(frame(serde_json::json!({"s": 6, "d": {"session_id": "session"}}))).await.unwrap();
+            ws.send(text_event(3, "m3")).await.unwrap();
+            ws.close(None).await.unwrap();
+        })
+        .await;
+        let mut session = resumable_session(2);
+        run_until_disconnect(&kook, url, &mut session).await;
+        assert_eq!(server.await.unwrap(), "/gateway?compress=0&resume=1&sn=2&session_id=session");
+
+        assert_eq!(session.session_id.as_deref(), Some("session"));
+        assert_eq!(session.max_sn(), 3);
+        assert_eq!(recv_event(&mut rx).await, "m3");
+    }
+
+    #[tokio::test]
+    async fn resume_failed_starts_fresh_session() {
+        let (kook, _rx) = test_kook();
+        let (url, server) = stand_in(|mut ws| async move {
+            ws.send(frame(serde_json::json!({"s": 1, "d": {"code": 0, "session_id": "session"}}))).await.unwrap();
+            let _resume = ws.next().await.unwrap().unwrap();
+            ws.send(frame(serde_json::json!({"s": 5, "d": {"code": 40108, "err": "invalid sn"}}))).await.unwrap();
+            let _ = ws.close(None).await;
+        })
+        .await;
+        let mut session = resumable_session(2);
+        run_until_disconnect(&kook, url.clone(), &mut session).await;
+        server.await.unwrap();
+
+        assert_eq!(session.session_id, None);
+        assert_eq!(session.max_sn(), 0);
+        assert_eq!(session.connect_url(&url), url);
+    }
+
+    #[tokio::test]
+    async fn other_reconnect_keeps_session() {
+        let (kook, _rx) = test_kook();
+        let (url, server) = stand_in(|mut ws| async move {
+            ws.send(frame(serde_json::json!({"s": 1, "d": {"code": 0, "session_id": "session"}}))).await.unwrap();
+            ws.send(text_event(1, "m1")).await.unwrap();
+            ws.send(frame(serde_json::json!({"s": 5, "d": {"code": 50000, "err": "server restart"}}))).await.unwrap();
+            let _ = ws.close(None).await;
+        })
+        .await;
+        let mut session = WsSession::new(SequenceConfig::default());
+        run_until_disconnect(&kook, url, &mut session).await;
+        server.await.unwrap();
+
+        assert_eq!(session.session_id.as_deref(), Some("session"));
+        assert_eq!(session.max_sn(), 1);
+    }
+
+    // 单线程运行时下任务按 spawn 顺序开始, 这里检查的是交给 dispatch 的顺序
+    #[tokio::test]
+    async fn events_are_delivered_in_sn_order() {
+        let (kook, mut rx) = test_kook();
+        let (url, server) = stand_in(|mut ws| async move {
+            ws.send(frame(serde_json::json!({"s": 1, "d": {"code": 0, "session_id": "session"}}))).await.unwrap();
+            for sn in [2, 1, 1, 3, 2] {
+                ws.send(text_event(sn, &format!("m{sn}"))).await.unwrap();
+            }
+            ws.close(None).await.unwrap();
+        })
+        .await;
+        let mut session = WsSession::new(SequenceConfig::default());
+        run_until_disconnect(&kook, url, &mut session).await;
+        server.await.unwrap();
+
+        assert_eq!(session.max_sn(), 3);
+        for expected in ["m1", "m2", "m3"] {
+            assert_eq!(recv_event(&mut rx).await, expected);
+        }
+        assert!(rx.try_recv().is_err());
+    }
+
+    // 处理越早的事件越慢, 只有 Sequential 能保证按 sn 顺序处理完
+    #[derive(Clone)]
+    struct Slow(mpsc::UnboundedSender<String>);
+
+    impl KookHandle for Slow {
+        type Err = KookError;
+
+        async fn on_event(&self, _kook: Arc<Kook<Self>>, event: Arc<Event>) -> Result<(), Self::Err> {
+            if let Event::Text(ref text) = *event {
+                let delay = match text.msg_id.as_str() {
+                    "m1" => 80,
+                    "m2" => 40,
+                    _ => 0,
+                };
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                let _ = self.0.send(text.msg_id.clone());
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn sequential_dispatch_finishes_in_sn_order() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let config = DispatchConfig {
+            strategy: DispatchStrategy::Sequential,
+            ..DispatchConfig::default()
+        };
+        let kook = Kook::with_bot(test_bot(), BotInfo { id: "bot".to_string() }, Slow(tx))
+            .with_dispatch_config(config)
+            .to_arc();
+        let (url, server) = stand_in(|mut ws| async move {
+            ws.send(frame(serde_json::json!({"s": 1, "d": {"code": 0, "session_id": "session"}}))).await.unwrap();
+            for sn in [2, 1, 3] {
+                ws.send(text_event(sn, &format!("m{sn}"))).await.unwrap();
+            }
+            ws.close(None).await.unwrap();
+        })
+        .await;
+        let mut session = WsSession::new(SequenceConfig::default());
+        run_until_disconnect(&kook, url, &mut session).await;
+        server.await.unwrap();
+
+        for expected in ["m1", "m2", "m3"] {
+            assert_eq!(recv_event(&mut rx).await, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_closes_websocket() {
+        let (kook, mut rx) = test_kook();
+        let (url, server) = stand_in(|mut ws| async move {
+            ws.send(frame(serde_json::json!({"s": 1, "d": {"code": 0, "session_id": "session"}}))).await.unwrap();
+            ws.send(text_event(1, "m1")).await.unwrap();
+            let msg = ws.next().await.unwrap().unwrap();
+            assert!(matches!(msg, WsMessage::Close(_)));
+        })
+        .await;
+        let session = WsSession::new(SequenceConfig::default());
+        let event_loop = tokio::spawn(kook.clone().run(WsStateMachine::ConnectGateway(url), session));
+        assert_eq!(recv_event(&mut rx).await, "m1");
+
+        kook.shutdown_handle().shutdown();
+        tokio::time::timeout(std::time::Duration::from_secs(5), event_loop)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        server.await.unwrap();
+    }
+
+    // 记录连接生命周期回调
+    #[derive(Clone)]
+    struct Lifecycle(mpsc::UnboundedSender<String>);
+
+    impl KookHandle for Lifecycle {
+        type Err = KookError;
+
+        async fn on_event(&self, _kook: Arc<Kook<Self>>, _event: Arc<Event>) -> Result<(), Self::Err> {
+            Ok(())
+        }
+
+        fn on_ready(&self, session_id: &str) {
+            let _ = self.0.send(format!("ready:{session_id}"));
+        }
+
+        fn on_resume(&self, session_id: &str) {
+            let _ = self.0.send(format!("resume:{session_id}"));
+        }
+
+        fn on_reconnect(&self, code: i32, _err: &str) {
+            let _ = self.0.send(format!("reconnect:{code}"));
+        }
+
+        fn on_disconnect(&self, reason: &DisconnectReason) {
+            let _ = self.0.send(format!("disconnect:{reason:?}"));
+        }
+
+        fn on_unknown_event(&self, event: &Value) {
+            let _ = self.0.send(format!("unknown:{}", event["type"]));
+        }
+    }
+
+    fn lifecycle_kook() -> (Arc<Kook<Lifecycle>>, mpsc::UnboundedReceiver<String>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let kook = Kook::with_bot(test_bot(), BotInfo { id: "bot".to_string() }, Lifecycle(tx));
+        (kook.to_arc(), rx)
+    }
+
+    fn drain(rx: &mut mpsc::UnboundedReceiver<String>) -> Vec<String> {
+        std::iter::from_fn(|| rx.try_recv().ok()).collect()
+    }
+
+    #[tokio::test]
+    async fn lifecycle_hooks_on_fresh_session() {
+        let (kook, mut rx) = lifecycle_kook();
+        let (url, server) = stand_in(|mut ws| async move {
+            ws.send(frame(serde_json::json!({"s": 1, "d": {"code": 0, "session_id": "session"}}))).await.unwrap();
+            ws.send(frame(serde_json::json!({"s": 0, "sn": 1, "d": {"type": 99}}))).await.unwrap();
+            ws.send(frame(serde_json::json!({"s": 5, "d": {"code": 40107, "err": "expired"}}))).await.unwrap();
+            let _ = ws.close(None).await;
+        })
+        .await;
+        let mut session = WsSession::new(SequenceConfig::default());
+        run_until_disconnect(&kook, url, &mut session).await;
+        server.await.unwrap();
+
+        assert_eq!(
+            drain(&mut rx),
+            vec![
+                "ready:session",
+                "unknown:99",
+                r#"disconnect:Reconnect { code: 40107, err: "expired" }"#,
+                "reconnect:40107"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn lifecycle_hooks_on_reconnect_before_hello() {
+        let (kook, mut rx) = lifecycle_kook();
+        let (url, server) = stand_in(|mut ws| async move {
+            ws.send(frame(serde_json::json!({"s": 5, "d": {"code": 40106, "err": "missing"}}))).await.unwrap();
+            let _ = ws.close(None).await;
+        })
+        .await;
+        let mut session = WsSession::new(SequenceConfig::default());
+        run_until_disconnect(&kook, url, &mut session).await;
+        server.await.unwrap();
+
+        assert_eq!(
+            drain(&mut rx),
+            vec![r#"disconnect:Reconnect { code: 40106, err: "missing" }"#, "reconnect:40106"]
+        );
+    }
+
+    #[tokio::test]
+    async fn lifecycle_hooks_on_resume() {
+        let (kook, mut rx) = lifecycle_kook();
+        let (url, server) = stand_in(|mut ws| async move {
+            ws.send(frame(serde_json::json!({"s": 1, "d": {"code": 0, "session_id": "session"}}))).await.unwrap();
+            let _resume = ws.next().await.unwrap().unwrap();
+            ws.send(frame(serde_json::json!({"s": 6, "d": {"session_id": "session"}}))).await.unwrap();
+            ws.close(None).await.unwrap();
+        })
+        .await;
+        let mut session = resumable_session(2);
+        run_until_disconnect(&kook, url, &mut session).await;
+        server.await.unwrap();
+
+        let calls = drain(&mut rx);
+        assert_eq!(calls[0], "resume:session");
+        assert!(calls[1].starts_with("disconnect:Error("));
+        assert_eq!(calls.len(), 2);
+    }
+
+    fn compressed(msg: WsMessage) -> WsMessage {
+        WsMessage::Binary(miniz_oxide::deflate::compress_to_vec_zlib(msg.to_text().unwrap().as_bytes(), 6))
+    }
+
+    #[tokio::test]
+    async fn compressed_frames_are_inflated() {
+        let (kook, mut rx) = test_kook();
+        let (url, server) = stand_in(|mut ws| async move {
+            ws.send(compressed(frame(serde_json::json!({"s": 1, "d": {"code": 0, "session_id": "session"}}))))
+                .await
+                .unwrap();
+            ws.send(WsMessage::Binary(b"not zlib".to_vec())).await.unwrap();
+            ws.send(compressed(text_event(1, "m1"))).await.unwrap();
+            ws.close(None).await.unwrap();
+        })
+        .await;
+        let mut session = WsSession::new(SequenceConfig::default());
+        run_until_disconnect(&kook, url, &mut session).await;
+        server.await.unwrap();
+
+        assert_eq!(session.session_id.as_deref(), Some("session"));
+        assert_eq!(recv_event(&mut rx).await, "m1");
+    }
+
+    #[test]
+    fn inflate_reports_decompress_error() {
+        assert!(matches!(Kook::<Recorder>::inflate(b"not zlib"), Err(KookError::Decompress(_))));
+        let invalid_utf8 = miniz_oxide::deflate::compress_to_vec_zlib(&[0xff, 0xfe], 6);
+        assert!(matches!(Kook::<Recorder>::inflate(&invalid_utf8), Err(KookError::Decompress(_))));
+    }
+
+    #[test]
+    fn reconnect_delay_grows_until_max() {
+        let policy = ReconnectPolicy {
+            jitter: 0.0,
+            max_delay: Duration::from_secs(5),
+            ..Default::default()
+        };
+        let delays: Vec<_> = (1..=5).map(|x| policy.delay(x).as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 5, 5]);
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn reconnect_delay_survives_degenerate_policy() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::ZERO,
+            ..Default::default()
+        };
+        assert_eq!(policy.delay(u32::MAX), Duration::ZERO);
+        let policy = ReconnectPolicy {
+            multiplier: f64::MAX,
+            jitter: f64::NAN,
+            max_delay: Duration::from_secs(5),
+            ..Default::default()
+        };
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(5));
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::ZERO,
+            multiplier: f64::INFINITY,
+            jitter: f64::INFINITY,
+            ..Default::default()
+        };
+        assert_eq!(policy.delay(3), policy.max_delay);
+
+        let policy = policy.normalized();
+        assert_eq!(policy.multiplier, 2.0);
+        assert_eq!(policy.jitter, 0.0);
+        let policy = ReconnectPolicy {
+            multiplier: 0.5,
+            jitter: 3.0,
+            ..Default::default()
+        }
+        .normalized();
+        assert_eq!(policy.multiplier, 1.0);
+        assert!(policy.jitter < 1.0);
+    }
+
+    #[tokio::test]
+    async fn hello_timeout_reconnects() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let kook = Kook::with_bot(test_bot(), BotInfo { id: "bot".to_string() }, Recorder(tx))
+            .with_reconnect_policy(ReconnectPolicy {
+                hello_timeout: Duration::from_millis(100),
+                ..Default::default()
+            })
+            .to_arc();
+        let (url, _server) = stand_in(|ws| async move {
+            // 不发送 hello
+            tokio::time::sleep(Duration::from_secs(3)).await;
+            drop(ws);
+        })
+        .await;
+        let mut session = WsSession::new(SequenceConfig::default());
+        let start = Instant::now();
+        run_until_disconnect(&kook, url, &mut session).await;
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn reconnect_delay_jitter_stays_in_range() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_secs(10),
+            jitter: 0.5,
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            let delay = policy.delay(1);
+            assert!(delay >= Duration::from_secs(5) && delay <= Duration::from_secs(15));
+        }
+    }
+
+    #[test]
+    fn reconnect_gives_up_after_max_attempts() {
+        let policy = ReconnectPolicy {
+            max_attempts: Some(3),
+            ..Default::default()
+        };
+        assert!(!policy.give_up(3));
+        assert!(policy.give_up(4));
+        assert!(!ReconnectPolicy::default().give_up(u32::MAX));
+    }
+
+    #[test]
+    fn sequencer_buffers_until_gap_is_filled() {
+        let mut sequencer = Sequencer::new(SequenceConfig::default());
+        assert!(sequencer.push(2, 2).is_empty());
+        assert!(sequencer.push(4, 4).is_empty());
+        assert!(sequencer.deadline().is_some());
+        assert_eq!(sequencer.push(1, 1), vec![1, 2]);
+        assert_eq!(sequencer.push(3, 3), vec![3, 4]);
+        assert!(sequencer.deadline().is_none());
+        assert!(sequencer.push(3, 3).is_empty());
+        assert!(sequencer.push(1, 1).is_empty());
+        assert_eq!(sequencer.last_sn, 4);
+    }
+
+    #[test]
+    fn sequencer_skips_gap_when_too_large() {
+        let mut sequencer = Sequencer::new(SequenceConfig {
+            max_gap: 2,
+            ..Default::default()
+        });
+        assert!(sequencer.push(3, 3).is_empty());
+        assert!(sequencer.push(5, 5).is_empty());
+        assert_eq!(sequencer.push(4, 4), vec![3, 4, 5]);
+        assert_eq!(sequencer.last_sn, 5);
+        assert!(sequencer.push(2, 2).is_empty());
+        assert_eq!(sequencer.push(6, 6), vec![6]);
+    }
+
+    #[test]
+    fn sequencer_skips_gap_after_timeout() {
+        let mut sequencer = Sequencer::new(SequenceConfig {
+            gap_timeout: Duration::from_secs(1),
+            ..Default::default()
+        });
+        assert!(sequencer.push(2, 2).is_empty());
+        let deadline = sequencer.deadline().unwrap();
+        assert!(sequencer.flush_expired(deadline - Duration::from_millis(1)).is_empty());
+        assert_eq!(sequencer.flush_expired(deadline), vec![2]);
+        assert!(sequencer.deadline().is_none());
+        assert_eq!(sequencer.push(3, 3), vec![3]);
+    }
+}
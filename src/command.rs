@@ -0,0 +1,619 @@
+use std::{future::Future, sync::Arc};
+
+use futures_util::{future::BoxFuture, FutureExt};
+
+use crate::{
+    api::event::{Event, Kmarkdown},
+    error::KookResult,
+    kook::{Kook, KookHandle},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CommandError {
+    #[error("unknown command `{0}`")]
+    UnknownCommand(String),
+    #[error("missing argument `{name}`, usage: {usage}")]
+    MissingArgument { name: String, usage: String },
+    #[error("invalid argument `{name}`: `{value}` is not a {expected}")]
+    InvalidArgument { name: String, value: String, expected: &'static str },
+    #[error("too many arguments, usage: {usage}")]
+    TooManyArguments { usage: String },
+    #[error("unterminated quote")]
+    UnterminatedQuote,
+}
+
+// 命令参数类型
+pub trait FromArg: Sized {
+    const TYPE_NAME: &'static str;
+    fn from_arg(arg: &str) -> Option<Self>;
+}
+
+macro_rules! from_arg_parse {
+    ($($t:ty => $name:literal),*) => {
+        $(impl FromArg for $t {
+            const TYPE_NAME: &'static str = $name;
+            fn from_arg(arg: &str) -> Option<Self> {
+                arg.parse().ok()
+            }
+        })*
+    };
+}
+
+from_arg_parse!(i32 => "int", i64 => "int", u32 => "int", u64 => "int", f64 => "number");
+
+impl FromArg for String {
+    const TYPE_NAME: &'static str = "text";
+    fn from_arg(arg: &str) -> Option<Self> {
+        Some(arg.to_string())
+    }
+}
+
+impl FromArg for bool {
+    const TYPE_NAME: &'static str = "bool";
+    fn from_arg(arg: &str) -> Option<Self> {
+        match arg.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Some(true),
+            "false" | "no" | "off" | "0" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+// (met)id(met), 也接受直接输入的 id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserMention(pub String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoleMention(pub u64);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelMention(pub String);
+
+fn strip_tag<'a>(arg: &'a str, tag: &str) -> Option<&'a str> {
+    let id = match arg.strip_prefix(tag) {
+        Some(rest) => rest.strip_suffix(tag)?,
+        None => arg,
+    };
+    (!id.is_empty() && id.chars().all(|c| c.is_ascii_digit())).then_some(id)
+}
+
+impl FromArg for UserMention {
+    const TYPE_NAME: &'static str = "user";
+    fn from_arg(arg: &str) -> Option<Self> {
+        strip_tag(arg, "(met)").map(|x| UserMention(x.to_string()))
+    }
+}
+
+impl FromArg for RoleMention {
+    const TYPE_NAME: &'static str = "role";
+    fn from_arg(arg: &str) -> Option<Self> {
+        strip_tag(arg, "(rol)").and_then(|x| x.parse().ok()).map(RoleMention)
+    }
+}
+
+impl FromArg for ChannelMention {
+    const TYPE_NAME: &'static str = "channel";
+    fn from_arg(arg: &str) -> Option<Self> {
+        strip_tag(arg, "(chn)").map(|x| ChannelMention(x.to_string()))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Param {
+    name: String,
+    type_name: &'static str,
+    optional: bool,
+    check: fn(&str) -> bool,
+}
+
+fn check<T: FromArg>(arg: &str) -> bool {
+    T::from_arg(arg).is_some()
+}
+
+#[derive(Debug, Clone)]
+pub struct Args {
+    params: Vec<Param>,
+    values: Vec<String>,
+    usage: String,
+}
+
+impl Args {
+    pub fn get<T: FromArg>(&self, name: &str) -> Result<T, CommandError> {
+        self.get_opt(name)?.ok_or_else(|| CommandError::MissingArgument {
+            name: name.to_string(),
+            usage: self.usage.clone(),
+        })
+    }
+
+    pub fn get_opt<T: FromArg>(&self, name: &str) -> Result<Option<T>, CommandError> {
+        let Some(value) = self.params.iter().position(|x| x.name == name).and_then(|i| self.values.get(i)) else {
+            return Ok(None);
+        };
+        T::from_arg(value).map(Some).ok_or_else(|| CommandError::InvalidArgument {
+            name: name.to_string(),
+            value: value.clone(),
+            expected: T::TYPE_NAME,
+        })
+    }
+
+    // 声明的参数之后多余的部分
+    pub fn rest(&self) -> &[String] {
+        self.values.get(self.params.len()..).unwrap_or_default()
+    }
+
+    pub fn raw(&self) -> &[String] {
+        &self.values
+    }
+}
+
+pub struct CommandContext {
+    pub event: Arc<Event>,
+    // 命中的命令名, 包含子命令
+    pub path: Vec<String>,
+    pub args: Args,
+}
+
+type Handler<H> = Box<dyn Fn(Arc<Kook<H>>, CommandContext) -> BoxFuture<'static, KookResult<()>> + Send + Sync>;
+
+pub struct Command<H: KookHandle + 'static> {
+    name: String,
+    aliases: Vec<String>,
+    description: String,
+    params: Vec<Param>,
+    variadic: bool,
+    subcommands: Vec<Command<H>>,
+    handler: Option<Handler<H>>,
+}
+
+impl<H: KookHandle + 'static> Command<H> {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            aliases: Vec::new(),
+            description: String::new(),
+            params: Vec::new(),
+            variadic: false,
+            subcommands: Vec::new(),
+            handler: None,
+        }
+    }
+
+    pub fn alias(mut self, alias: impl Into<String>) -> Self {
+        self.aliases.push(alias.into());
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    pub fn arg<T: FromArg>(mut self, name: impl Into<String>) -> Self {
+        self.params.push(Param {
+            name: name.into(),
+            type_name: T::TYPE_NAME,
+            optional: false,
+            check: check::<T>,
+        });
+        self
+    }
+
+    // 可选参数需要放在必填参数之后
+    pub fn optional_arg<T: FromArg>(mut self, name: impl Into<String>) -> Self {
+        self.params.push(Param {
+            name: name.into(),
+            type_name: T::TYPE_NAME,
+            optional: true,
+            check: check::<T>,
+        });
+        self
+    }
+
+    // 允许多余的参数, 通过 Args::rest 获取
+    pub fn variadic(mut self) -> Self {
+        self.variadic = true;
+        self
+    }
+
+    pub fn subcommand(mut self, command: Command<H>) -> Self {
+        self.subcommands.push(command);
+        self
+    }
+
+    pub fn handler<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(Arc<Kook<H>>, CommandContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = KookResult<()>> + Send + 'static,
+    {
+        self.handler = Some(Box::new(move |kook, ctx| handler(kook, ctx).boxed()));
+        self
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|x| x == name)
+    }
+
+    fn usage(&self, prefix: &str, path: &str) -> String {
+        let mut usage = format!("{prefix}{path}");
+        for param in self.params.iter() {
+            match param.optional {
+                true => usage.push_str(&format!(" [{}:{}]", param.name, param.type_name)),
+                false => usage.push_str(&format!(" <{}:{}>", param.name, param.type_name)),
+            }
+        }
+        if self.variadic {
+            usage.push_str(" ...");
+        }
+        usage
+    }
+
+    fn write_help(&self, prefix: &str, parent: &str, ret: &mut String) {
+        let path = match parent.is_empty() {
+            true => self.name.clone(),
+            false => format!("{parent} {}", self.name),
+        };
+        if self.handler.is_some() {
+            ret.push_str(&self.usage(prefix, &path));
+            if !self.description.is_empty() {
+                ret.push_str(" - ");
+                ret.push_str(&self.description);
+            }
+            if !self.aliases.is_empty() {
+                ret.push_str(&format!(" (aliases: {})", self.aliases.join(", ")));
+            }
+            ret.push('\n');
+        }
+        for sub in self.subcommands.iter() {
+            sub.write_help(prefix, &path, ret);
+        }
+    }
+
+    fn args(&self, prefix: &str, path: &str, values: Vec<String>) -> Result<Args, CommandError> {
+        let usage = self.usage(prefix, path);
+        for (i, param) in self.params.iter().enumerate() {
+            match values.get(i) {
+                None if param.optional => break,
+                None => {
+                    return Err(CommandError::MissingArgument {
+                        name: param.name.clone(),
+                        usage,
+                    })
+                }
+                Some(value) if !(param.check)(value) => {
+                    return Err(CommandError::InvalidArgument {
+                        name: param.name.clone(),
+                        value: value.clone(),
+                        expected: param.type_name,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        if values.len() > self.params.len() && !self.variadic {
+            return Err(CommandError::TooManyArguments { usage });
+        }
+        Ok(Args {
+            params: self.params.clone(),
+            values,
+            usage,
+        })
+    }
+}
+
+pub struct CommandFramework<H: KookHandle + 'static> {
+    prefixes: Vec<String>,
+    mention: bool,
+    report_unknown: bool,
+    commands: Vec<Command<H>>,
+}
+
+impl<H: KookHandle + 'static> Default for CommandFramework<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: KookHandle + 'static> CommandFramework<H> {
+    pub fn new() -> Self {
+        Self {
+            prefixes: Vec::new(),
+            mention: true,
+            report_unknown: false,
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefixes.push(prefix.into());
+        self
+    }
+
+    // 以 @机器人 开头的消息也视为命令, 默认开启
+    pub fn with_mention(mut self, mention: bool) -> Self {
+        self.mention = mention;
+        self
+    }
+
+    // 未知命令默认忽略, 开启后返回 CommandError::UnknownCommand
+    pub fn with_report_unknown(mut self, report_unknown: bool) -> Self {
+        self.report_unknown = report_unknown;
+        self
+    }
+
+    pub fn command(mut self, command: Command<H>) -> Self {
+        self.commands.push(command);
+        self
+    }
+
+    pub fn help(&self) -> String {
+        let prefix = self.prefixes.first().map(String::as_str).unwrap_or_default();
+        let mut ret = String::new();
+        for command in self.commands.iter() {
+            command.write_help(prefix, "", &mut ret);
+        }
+        ret
+    }
+
+    // 返回是否执行了命令, 不是命令或未知命令的消息返回 false
+    pub async fn handle(&self, kook: &Arc<Kook<H>>, event: Arc<Event>) -> KookResult<bool> {
+        let (content, kmarkdown) = match *event {
+            Event::Text(ref e) => (e.content.as_str(), None),
+            Event::KMarkdown(ref e) => (e.content.as_str(), Some(&e.extra.kmarkdown)),
+            _ => return Ok(false),
+        };
+        let Some((prefix, text)) = self.strip_trigger(&kook.bot_info.id, content, kmarkdown) else {
+            return Ok(false);
+        };
+        let mut tokens = tokenize(text)?.into_iter();
+        let Some(name) = tokens.next() else {
+            return Ok(false);
+        };
+        let Some(mut command) = self.commands.iter().find(|x| x.matches(&name)) else {
+            return self.unknown(name);
+        };
+        let mut path = vec![command.name.clone()];
+        let mut tokens = tokens.peekable();
+        while let Some(sub) = tokens.peek().and_then(|x| command.subcommands.iter().find(|sub| sub.matches(x))) {
+            tokens.next();
+            path.push(sub.name.clone());
+            command = sub;
+        }
+        let Some(ref handler) = command.handler else {
+            let mut unknown = path.join(" ");
+            if let Some(token) = tokens.next() {
+                unknown.push(' ');
+                unknown.push_str(&token);
+            }
+            return self.unknown(unknown);
+        };
+        let args = command.args(prefix, &path.join(" "), tokens.collect())?;
+        handler(kook.clone(), CommandContext { event, path, args }).await?;
+        Ok(true)
+    }
+
+    fn unknown(&self, name: String) -> KookResult<bool> {
+        match self.report_unknown {
+            true => Err(CommandError::UnknownCommand(name).into()),
+            false => Ok(false),
+        }
+    }
+
+    // 去掉 @机器人 和前缀, 返回使用的前缀和剩余的内容
+    fn strip_trigger<'a>(&'a self, bot_id: &str, content: &'a str, kmarkdown: Option<&'a Kmarkdown>) -> Option<(&'a str, &'a str)> {
+        let mut text = content.trim_start();
+        let mut mentioned = false;
+        if self.mention {
+            if let Some(rest) = text.strip_prefix(&format!("(met){bot_id}(met)")) {
+                text = rest.trim_start();
+                mentioned = true;
+            } else if let Some(kmarkdown) = kmarkdown {
+                let raw = kmarkdown.raw_content.trim_start();
+                let rest = kmarkdown
+                    .mention_part
+                    .iter()
+                    .filter(|x| x.id == bot_id)
+                    .flat_map(|x| [&x.full_name, &x.username])
+                    .find_map(|name| raw.strip_prefix('@')?.strip_prefix(name.as_str()));
+                if let Some(rest) = rest {
+                    text = rest.trim_start();
+                    mentioned = true;
+                }
+            }
+        }
+        for prefix in self.prefixes.iter() {
+            if let Some(rest) = text.strip_prefix(prefix.as_str()) {
+                return Some((prefix, rest));
+            }
+        }
+        mentioned.then_some(("", text))
+    }
+}
+
+// 按空白分割, 支持引号和反斜杠转义
+fn tokenize(text: &str) -> Result<Vec<String>, CommandError> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut quote = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (_, '\\') => {
+                current.push(chars.next().unwrap_or('\\'));
+                in_token = true;
+            }
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') if !in_token => {
+                quote = Some(c);
+                in_token = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err(CommandError::UnterminatedQuote);
+    }
+    if in_token {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        api::testing::{test_kook, text_event_body, Recorder},
+        KookError,
+    };
+
+    fn text(content: &str) -> Arc<Event> {
+        let mut body = text_event_body("m");
+        body["content"] = content.into();
+        Arc::new(serde_json::from_value(body).unwrap())
+    }
+
+    fn kmarkdown(content: &str, raw_content: &str) -> Arc<Event> {
+        let mut body = text_event_body("m");
+        body["type"] = 9.into();
+        body["content"] = content.into();
+        let extra = &mut body["extra"];
+        extra["nav_channels"] = serde_json::json!([]);
+        extra["author"] = serde_json::json!({
+            "id": "user", "username": "user", "identify_num": "0001", "online": true, "os": "Websocket",
+            "status": 1, "avatar": "", "nickname": "user", "roles": []
+        });
+        extra["kmarkdown"] = serde_json::json!({
+            "raw_content": raw_content,
+            "mention_part": [{"id": "bot", "username": "Bot", "full_name": "Bot#0001", "avatar": ""}],
+            "mention_role_part": []
+        });
+        Arc::new(serde_json::from_value(body).unwrap())
+    }
+
+    fn framework(calls: Arc<Mutex<Vec<String>>>) -> CommandFramework<Recorder> {
+        let roll = calls.clone();
+        let add = calls.clone();
+        CommandFramework::new()
+            .prefix("!")
+            .command(
+                Command::new("roll")
+                    .alias("r")
+                    .description("掷骰子")
+                    .arg::<u32>("sides")
+                    .optional_arg::<String>("label")
+                    .handler(move |_kook, ctx| {
+                        let roll = roll.clone();
+                        async move {
+                            let sides: u32 = ctx.args.get("sides")?;
+                            let label: Option<String> = ctx.args.get_opt("label")?;
+                            roll.lock().unwrap().push(format!("roll {} {:?}", sides, label));
+                            Ok(())
+                        }
+                    }),
+            )
+            .command(
+                Command::new("role").subcommand(
+                    Command::new("add")
+                        .arg::<UserMention>("user")
+                        .arg::<RoleMention>("role")
+                        .handler(move |_kook, ctx| {
+                            let add = add.clone();
+                            async move {
+                                let user: UserMention = ctx.args.get("user")?;
+                                let role: RoleMention = ctx.args.get("role")?;
+                                add.lock().unwrap().push(format!("{} add {} {}", ctx.path.join(" "), user.0, role.0));
+                                Ok(())
+                            }
+                        }),
+                ),
+            )
+    }
+
+    #[tokio::test]
+    async fn runs_commands_with_typed_args() {
+        let (kook, _rx) = test_kook();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let framework = framework(calls.clone());
+        assert!(framework.handle(&kook, text("!roll 6 \"two words\"")).await.unwrap());
+        assert!(framework.handle(&kook, text("!r 20")).await.unwrap());
+        assert!(framework.handle(&kook, text("(met)bot(met) role add (met)42(met) (rol)7(rol)")).await.unwrap());
+        assert!(framework.handle(&kook, kmarkdown("ignored", "@Bot#0001 roll 4")).await.unwrap());
+        assert!(!framework.handle(&kook, text("hello !roll 6")).await.unwrap());
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "roll 6 Some(\"two words\")".to_string(),
+                "roll 20 None".to_string(),
+                "role add add 42 7".to_string(),
+                "roll 4 None".to_string(),
+            ]
+        );
+    }
+
+    async fn error(framework: &CommandFramework<Recorder>, content: &str) -> CommandError {
+        let (kook, _rx) = test_kook();
+        match framework.handle(&kook, text(content)).await {
+            Err(KookError::Command(err)) => err,
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_bad_arguments() {
+        let framework = framework(Arc::new(Mutex::new(Vec::new()))).with_report_unknown(true);
+        assert_eq!(error(&framework, "!nope").await, CommandError::UnknownCommand("nope".to_string()));
+        assert_eq!(error(&framework, "!role remove").await, CommandError::UnknownCommand("role remove".to_string()));
+        assert_eq!(
+            error(&framework, "!roll").await,
+            CommandError::MissingArgument {
+                name: "sides".to_string(),
+                usage: "!roll <sides:int> [label:text]".to_string()
+            }
+        );
+        assert_eq!(
+            error(&framework, "!roll six").await,
+            CommandError::InvalidArgument {
+                name: "sides".to_string(),
+                value: "six".to_string(),
+                expected: "int"
+            }
+        );
+        assert!(matches!(error(&framework, "!roll 6 a b").await, CommandError::TooManyArguments { .. }));
+        assert_eq!(error(&framework, "!roll 6 \"open").await, CommandError::UnterminatedQuote);
+    }
+
+    #[tokio::test]
+    async fn ignores_unknown_commands_by_default() {
+        let (kook, _rx) = test_kook();
+        let framework = framework(Arc::new(Mutex::new(Vec::new())));
+        assert!(!framework.handle(&kook, text("!nope")).await.unwrap());
+        assert!(!framework.handle(&kook, text("!role remove")).await.unwrap());
+    }
+
+    #[test]
+    fn generates_help() {
+        let framework = framework(Arc::new(Mutex::new(Vec::new())));
+        assert_eq!(
+            framework.help(),
+            "!roll <sides:int> [label:text] - 掷骰子 (aliases: r)\n!role add <user:user> <role:role>\n"
+        );
+    }
+
+    #[test]
+    fn tokenizes_quotes_and_escapes() {
+        assert_eq!(tokenize(r#"a "b c" 'd' e\ f don't"#).unwrap(), vec!["a", "b c", "d", "e f", "don't"]);
+    }
+}
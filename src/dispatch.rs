@@ -0,0 +1,338 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    panic::AssertUnwindSafe,
+    sync::{Arc, Mutex},
+};
+
+use futures_util::FutureExt;
+
+use crate::{
+    api::event::Event,
+    kook::{Kook, KookHandle},
+    middleware::{Context, Next},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DispatchStrategy {
+    // 每个事件一个任务, 不保证处理顺序
+    // 最多同时处理 backlog 个事件, 之后的事件排队, 队列最多积压 backlog 个
+    #[default]
+    Bounded,
+    // 全局最多同时处理 limit 个事件
+    Concurrent { limit: usize },
+    // 同一个 target_id 的事件按顺序处理
+    PerTarget,
+    // 同一个服务器的事件按顺序处理, 私聊按 target_id
+    PerGuild,
+    // 所有事件依次处理, 网关事件严格按 sn 顺序
+    Sequential,
+}
+
+// 队列满时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    #[default]
+    DropNewest,
+    DropOldest,
+}
+
+#[derive(Debug, Clone)]
+pub struct DispatchConfig {
+    pub strategy: DispatchStrategy,
+    // 每个队列最多积压多少个等待处理的事件
+    pub backlog: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for DispatchConfig {
+    fn default() -> Self {
+        Self {
+            strategy: DispatchStrategy::default(),
+            backlog: 1024,
+            overflow: OverflowPolicy::default(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    events: VecDeque<Arc<Event>>,
+    workers: usize,
+}
+
+#[derive(Default)]
+pub(crate) struct Dispatcher {
+    config: DispatchConfig,
+    queues: Mutex<HashMap<String, Queue>>,
+}
+
+impl Dispatcher {
+    pub(crate) fn new(config: DispatchConfig) -> Self {
+        Self {
+            config,
+            queues: Mutex::default(),
+        }
+    }
+
+    // 返回队列名和该队列最多几个并发
+    fn route(&self, event: &Event) -> (String, usize) {
+        match self.config.strategy {
+            DispatchStrategy::Bounded => (String::new(), self.config.backlog.max(1)),
+            DispatchStrategy::Concurrent { limit } => (String::new(), limit.max(1)),
+            DispatchStrategy::PerTarget => (event.target_id().to_string(), 1),
+            DispatchStrategy::PerGuild => (event.guild_id().unwrap_or(event.target_id()).to_string(), 1),
+            DispatchStrategy::Sequential => (String::new(), 1),
+        }
+    }
+
+    // 有空闲位置时直接返回事件, 由新的工作任务处理, 否则入队
+    fn push(&self, key: &str, max_workers: usize, event: Arc<Event>) -> Option<Arc<Event>> {
+        let mut queues = self.queues.lock().unwrap();
+        let queue = queues.entry(key.to_string()).or_default();
+        if queue.workers < max_workers {
+            queue.workers += 1;
+            return Some(event);
+        }
+        if queue.events.len() >= self.config.backlog {
+            if self.config.overflow == OverflowPolicy::DropNewest || queue.events.is_empty() {
+                tracing::warn!("dispatch queue `{}` full, drop msg_id:{}", key, event.msg_id());
+                return None;
+            }
+            if let Some(oldest) = queue.events.pop_front() {
+                tracing::warn!("dispatch queue `{}` full, drop msg_id:{}", key, oldest.msg_id());
+            }
+        }
+        queue.events.push_back(event);
+        None
+    }
+
+    fn pop(&self, key: &str) -> Option<Arc<Event>> {
+        let mut queues = self.queues.lock().unwrap();
+        let queue = queues.get_mut(key)?;
+        let event = queue.events.pop_front();
+        if event.is_none() {
+            queue.workers -= 1;
+            if queue.workers == 0 {
+                queues.remove(key);
+            }
+        }
+        event
+    }
+}
+
+impl<H: KookHandle> Kook<H> {
+    pub(crate) fn dispatch(self: &Arc<Self>, event: Event) {
+        let event = event.to_arc();
+        let kook = self.clone();
+        let (key, max_workers) = self.dispatcher.route(&event);
+        if let Some(event) = self.dispatcher.push(&key, max_workers, event) {
+            self.spawn_handler(async move {
+                let mut next = Some(event);
+                while let Some(event) = next {
+                    kook.run_middleware(event).await;
+                    next = kook.dispatcher.pop(&key);
+                }
+            });
+        }
+    }
+
+    fn spawn_handler(&self, fut: impl Future<Output = ()> + Send + 'static) {
+        let abort = self.abort.clone();
+        self.tasks.spawn(async move {
+            tokio::select! {
+                _ = fut => {},
+                _ = abort.cancelled() => tracing::warn!("handler cancelled by shutdown"),
+            }
+        });
+    }
+
+    // 处理函数 panic 时只记录日志, 工作任务继续处理队列
+    async fn run_middleware(self: &Arc<Self>, event: Arc<Event>) {
+        let endpoint = |event: Arc<Event>| {
+            let kook = self.clone();
+            async move {
+                if let Err(err) = kook.handle.on_event(kook.clone(), event).await {
+                    kook.handle.error_handle(&err);
+                }
+            }
+            .boxed()
+        };
+        let ctx = Context {
+            bot: &self.bot,
+            bot_id: &self.bot_info.id,
+        };
+        let msg_id = event.msg_id().to_string();
+        let ret = AssertUnwindSafe(Next::new(ctx, &self.middleware, &endpoint).run(event)).catch_unwind().await;
+        if ret.is_err() {
+            tracing::error!("event handler panicked, msg_id:{}", msg_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use tokio::sync::Semaphore;
+
+    use super::*;
+    use crate::{
+        api::testing::{test_bot, text_event_body},
+        kook::BotInfo,
+        KookError,
+    };
+
+    // 每个事件需要拿到 gate 的许可才能结束
+    #[derive(Clone)]
+    struct Gate {
+        log: Arc<Mutex<Vec<String>>>,
+        gate: Arc<Semaphore>,
+        active: Arc<AtomicUsize>,
+        max_active: Arc<AtomicUsize>,
+    }
+
+    impl KookHandle for Gate {
+        type Err = KookError;
+
+        async fn on_event(&self, _kook: Arc<Kook<Self>>, event: Arc<Event>) -> Result<(), Self::Err> {
+            if event.msg_id() == "panic" {
+                panic!("handler panicked");
+            }
+            let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_active.fetch_max(active, Ordering::SeqCst);
+            self.log.lock().unwrap().push(event.msg_id().to_string());
+            self.gate.acquire().await.unwrap().forget();
+            self.active.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn kook(config: DispatchConfig) -> (Arc<Kook<Gate>>, Gate) {
+        let gate = Gate {
+            log: Arc::default(),
+            gate: Arc::new(Semaphore::new(0)),
+            active: Arc::default(),
+            max_active: Arc::default(),
+        };
+        let kook = Kook::with_bot(test_bot(), BotInfo { id: "bot".to_string() }, gate.clone()).with_dispatch_config(config);
+        (kook.to_arc(), gate)
+    }
+
+    fn event(msg_id: &str, target_id: &str) -> Event {
+        let mut body = text_event_body(msg_id);
+        body["target_id"] = target_id.into();
+        serde_json::from_value(body).unwrap()
+    }
+
+    async fn finish(kook: &Arc<Kook<Gate>>, gate: &Gate) -> Vec<String> {
+        gate.gate.add_permits(1000);
+        kook.tasks.close();
+        tokio::time::timeout(Duration::from_secs(5), kook.tasks.wait()).await.unwrap();
+        gate.log.lock().unwrap().clone()
+    }
+
+    async fn settle() {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    fn config(strategy: DispatchStrategy) -> DispatchConfig {
+        DispatchConfig {
+            strategy,
+            ..DispatchConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn sequential_runs_one_at_a_time_in_order() {
+        let (kook, gate) = kook(config(DispatchStrategy::Sequential));
+        for i in 0..5 {
+            kook.dispatch(event(&i.to_string(), &format!("channel{}", i % 2)));
+        }
+        settle().await;
+        assert_eq!(gate.active.load(Ordering::SeqCst), 1);
+        assert_eq!(finish(&kook, &gate).await, vec!["0", "1", "2", "3", "4"]);
+        assert_eq!(gate.max_active.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn per_target_orders_each_channel() {
+        let (kook, gate) = kook(config(DispatchStrategy::PerTarget));
+        for i in 0..6 {
+            kook.dispatch(event(&format!("{}-{}", i % 2, i), &format!("channel{}", i % 2)));
+        }
+        settle().await;
+        assert_eq!(gate.active.load(Ordering::SeqCst), 2);
+        let log = finish(&kook, &gate).await;
+        for channel in ["0-", "1-"] {
+            let order: Vec<_> = log.iter().filter(|x| x.starts_with(channel)).collect();
+            let mut sorted = order.clone();
+            sorted.sort();
+            assert_eq!(order, sorted);
+            assert_eq!(order.len(), 3);
+        }
+        assert_eq!(gate.max_active.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn concurrent_limits_running_handlers() {
+        let (kook, gate) = kook(config(DispatchStrategy::Concurrent { limit: 3 }));
+        for i in 0..10 {
+            kook.dispatch(event(&i.to_string(), "channel"));
+        }
+        settle().await;
+        assert_eq!(gate.active.load(Ordering::SeqCst), 3);
+        assert_eq!(finish(&kook, &gate).await.len(), 10);
+        assert_eq!(gate.max_active.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn overflow_drops_newest_or_oldest() {
+        for (overflow, expected) in [
+            (OverflowPolicy::DropNewest, vec!["0", "1", "2"]),
+            (OverflowPolicy::DropOldest, vec!["0", "3", "4"]),
+        ] {
+            let (kook, gate) = kook(DispatchConfig {
+                strategy: DispatchStrategy::Sequential,
+                backlog: 2,
+                overflow,
+            });
+            kook.dispatch(event("0", "channel"));
+            settle().await;
+            for i in 1..5 {
+                kook.dispatch(event(&i.to_string(), "channel"));
+            }
+            assert_eq!(finish(&kook, &gate).await, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn panicking_handler_does_not_stall_queue() {
+        let (kook, gate) = kook(config(DispatchStrategy::Sequential));
+        kook.dispatch(event("panic", "channel"));
+        kook.dispatch(event("1", "channel"));
+        settle().await;
+        assert_eq!(gate.active.load(Ordering::SeqCst), 1);
+        assert_eq!(finish(&kook, &gate).await, vec!["1"]);
+    }
+
+    #[tokio::test]
+    async fn bounded_queues_beyond_backlog() {
+        let (kook, gate) = kook(DispatchConfig {
+            strategy: DispatchStrategy::Bounded,
+            backlog: 2,
+            overflow: OverflowPolicy::DropNewest,
+        });
+        for i in 0..6 {
+            kook.dispatch(event(&i.to_string(), "channel"));
+        }
+        settle().await;
+        assert_eq!(gate.active.load(Ordering::SeqCst), 2);
+        let mut log = finish(&kook, &gate).await;
+        log.sort();
+        assert_eq!(log, vec!["0", "1", "2", "3"]);
+    }
+}
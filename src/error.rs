@@ -1,18 +1,54 @@
 #[derive(thiserror::Error, Debug)]
 pub enum KookError {
     #[error("webscocket error `{0}`")]
-    Websocket(#[from] tokio_tungstenite::tungstenite::Error),
+    Websocket(Box<tokio_tungstenite::tungstenite::Error>),
     #[error("http error `{0}`")]
     Http(#[from] reqwest::Error),
     #[error("json error `{0}`")]
     Json(#[from] serde_json::Error),
+    #[error("webhook server error `{0}`")]
+    Hyper(#[from] hyper::Error),
+    #[error("webhook error:`{0}`")]
+    Webhook(String),
+    #[error("io error `{0}`")]
+    Io(#[from] std::io::Error),
+    #[error("decompress error `{0}`")]
+    Decompress(String),
     #[error("api error code:`{code}` message:`{message}`")]
     Api{
         code: i32,
         message: String
     },
+    #[error("reconnect gave up after `{attempts}` attempts")]
+    Reconnect {
+        attempts: u32,
+    },
+    #[error("rate limited on `{route}`, retry after `{retry_after:?}`")]
+    RateLimited {
+        route: String,
+        retry_after: std::time::Duration,
+    },
+    #[error("card error:`{0}`")]
+    Card(String),
+    #[error("interaction error:`{0}`")]
+    Interaction(String),
+    #[error("command error:`{0}`")]
+    Command(#[from] crate::command::CommandError),
     #[error("custom error:`{0}`")]
     Custom(String)
 }
 
+// tungstenite 的错误比较大, 装箱后避免 KookResult 过大
+impl From<tokio_tungstenite::tungstenite::Error> for KookError {
+    fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        KookError::Websocket(Box::new(err))
+    }
+}
+
+impl From<miniz_oxide::inflate::DecompressError> for KookError {
+    fn from(err: miniz_oxide::inflate::DecompressError) -> Self {
+        KookError::Decompress(err.to_string())
+    }
+}
+
 pub type KookResult<T> = Result<T, KookError>;
@@ -0,0 +1,285 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    future::Future,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures_util::{future::BoxFuture, FutureExt};
+
+use crate::{
+    api::{
+        card::{Element, Theme},
+        event::{Event, SystemEvent, SystemExtra},
+    },
+    error::{KookError, KookResult},
+    kook::{Kook, KookHandle},
+};
+
+// 过期的记录保留一段时间, 之后的点击按未注册处理
+const EXPIRED_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+// 一次按钮点击, state 为从 value 中解析出的数据
+#[derive(Debug, Clone)]
+pub struct ButtonClick<T> {
+    pub state: T,
+    pub value: String,
+    pub msg_id: String,
+    pub user_id: String,
+    pub target_id: String,
+    pub channel_type: String,
+}
+
+impl ButtonClick<()> {
+    pub(crate) fn from_event(event: &Event) -> Option<Self> {
+        let Event::System(SystemEvent {
+            channel_type,
+            extra: SystemExtra::MessageBtnClick { value, msg_id, user_id, target_id },
+            ..
+        }) = event
+        else {
+            return None;
+        };
+        Some(ButtonClick {
+            state: (),
+            value: value.clone(),
+            msg_id: msg_id.clone(),
+            user_id: user_id.clone(),
+            target_id: target_id.clone(),
+            channel_type: channel_type.clone(),
+        })
+    }
+
+    fn with_state<T>(&self, state: T) -> ButtonClick<T> {
+        ButtonClick {
+            state,
+            value: self.value.clone(),
+            msg_id: self.msg_id.clone(),
+            user_id: self.user_id.clone(),
+            target_id: self.target_id.clone(),
+            channel_type: self.channel_type.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteOutcome {
+    Handled,
+    // 按钮所在消息已过期
+    Expired,
+    // 没有匹配的处理函数
+    Unmatched,
+    // 不是按钮点击事件
+    Ignored,
+}
+
+// 按钮的 value 由前缀和数据拼接而成
+pub fn button_value(prefix: &str, state: impl Display) -> String {
+    format!("{prefix}{state}")
+}
+
+pub fn button(theme: Theme, text: impl Into<String>, prefix: &str, state: impl Display) -> Element {
+    Element::button(theme, text, button_value(prefix, state))
+}
+
+type Route<H> = Box<dyn Fn(&Arc<Kook<H>>, &ButtonClick<()>) -> Option<BoxFuture<'static, KookResult<()>>> + Send + Sync>;
+
+pub struct InteractionRouter<H: KookHandle + 'static> {
+    routes: Vec<Route<H>>,
+    expiry: Mutex<HashMap<String, Instant>>,
+}
+
+impl<H: KookHandle + 'static> Default for InteractionRouter<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: KookHandle + 'static> InteractionRouter<H> {
+    pub fn new() -> Self {
+        Self {
+            routes: Vec::new(),
+            expiry: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // value 以 prefix 开头时, 剩余部分通过 FromStr 解析为 state
+    pub fn on<T, F, Fut>(mut self, prefix: impl Into<String>, handler: F) -> Self
+    where
+        T: FromStr + Send + 'static,
+        F: Fn(Arc<Kook<H>>, ButtonClick<T>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = KookResult<()>> + Send + 'static,
+    {
+        let prefix = prefix.into();
+        self.routes.push(Box::new(move |kook, click| {
+            let rest = click.value.strip_prefix(prefix.as_str())?;
+            let fut = match rest.parse::<T>() {
+                Ok(state) => handler(kook.clone(), click.with_state(state)).boxed(),
+                Err(_) => {
+                    let err = KookError::Interaction(format!("decode button value `{}` with prefix `{}` failed", click.value, prefix));
+                    async move { Err(err) }.boxed()
+                }
+            };
+            Some(fut)
+        }));
+        self
+    }
+
+    // pattern 返回 Some 时视为匹配
+    pub fn on_pattern<T, P, F, Fut>(mut self, pattern: P, handler: F) -> Self
+    where
+        T: Send + 'static,
+        P: Fn(&str) -> Option<T> + Send + Sync + 'static,
+        F: Fn(Arc<Kook<H>>, ButtonClick<T>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = KookResult<()>> + Send + 'static,
+    {
+        self.routes.push(Box::new(move |kook, click| {
+            let state = pattern(&click.value)?;
+            Some(handler(kook.clone(), click.with_state(state)).boxed())
+        }));
+        self
+    }
+
+    // 到期后该消息上的按钮点击不再路由
+    // is_none_or 需要 Rust 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn expire_at(&self, msg_id: impl Into<String>, deadline: Instant) {
+        let mut expiry = self.expiry.lock().unwrap();
+        let now = Instant::now();
+        expiry.retain(|_, x| x.checked_add(EXPIRED_RETENTION).map_or(true, |x| x > now));
+        expiry.insert(msg_id.into(), deadline);
+    }
+
+    // timeout 过大 (例如 Duration::MAX) 时视为永不过期
+    pub fn expire_after(&self, msg_id: impl Into<String>, timeout: Duration) {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.expire_at(msg_id, deadline),
+            None => self.forget(&msg_id.into()),
+        }
+    }
+
+    pub fn forget(&self, msg_id: &str) {
+        self.expiry.lock().unwrap().remove(msg_id);
+    }
+
+    // 使用 EventRouter::with_interactions 时会自动调用, 其他 KookHandle 在 on_event 中调用
+    pub async fn handle(&self, kook: &Arc<Kook<H>>, event: &Event) -> KookResult<RouteOutcome> {
+        let Some(click) = ButtonClick::from_event(event) else {
+            return Ok(RouteOutcome::Ignored);
+        };
+        let expired = self.expiry.lock().unwrap().get(&click.msg_id).is_some_and(|x| *x <= Instant::now());
+        if expired {
+            tracing::debug!("button click on expired message:{}", click.msg_id);
+            return Ok(RouteOutcome::Expired);
+        }
+        for route in self.routes.iter() {
+            if let Some(fut) = route(kook, &click) {
+                fut.await?;
+                return Ok(RouteOutcome::Handled);
+            }
+        }
+        Ok(RouteOutcome::Unmatched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::testing::{test_kook, Recorder};
+
+    fn click(value: &str, msg_id: &str) -> Event {
+        serde_json::from_value(serde_json::json!({
+            "channel_type": "GROUP",
+            "type": 255,
+            "target_id": "channel",
+            "author_id": "1",
+            "content": "[系统消息]",
+            "extra": {
+                "type": "message_btn_click",
+                "body": {"value": value, "msg_id": msg_id, "user_id": "user", "target_id": "channel"}
+            },
+            "msg_id": "system",
+            "msg_timestamp": 0,
+            "nonce": ""
+        }))
+        .unwrap()
+    }
+
+    fn router(clicks: Arc<Mutex<Vec<String>>>) -> InteractionRouter<Recorder> {
+        let votes = clicks.clone();
+        InteractionRouter::new()
+            .on("vote:", move |_kook, click: ButtonClick<u32>| {
+                let votes = votes.clone();
+                async move {
+                    votes.lock().unwrap().push(format!("vote {} by {}", click.state, click.user_id));
+                    Ok(())
+                }
+            })
+            .on_pattern(
+                |value| value.split_once('/').map(|(a, b)| (a.to_string(), b.to_string())),
+                move |_kook, click| {
+                    let clicks = clicks.clone();
+                    async move {
+                        clicks.lock().unwrap().push(format!("{} {}", click.state.0, click.state.1));
+                        Ok(())
+                    }
+                },
+            )
+    }
+
+    #[tokio::test]
+    async fn routes_by_prefix_and_pattern() {
+        let (kook, _rx) = test_kook();
+        let clicks = Arc::new(Mutex::new(Vec::new()));
+        let router = router(clicks.clone());
+        assert_eq!(router.handle(&kook, &click(&button_value("vote:", 3), "m")).await.unwrap(), RouteOutcome::Handled);
+        assert_eq!(router.handle(&kook, &click("page/2", "m")).await.unwrap(), RouteOutcome::Handled);
+        assert_eq!(router.handle(&kook, &click("other", "m")).await.unwrap(), RouteOutcome::Unmatched);
+        assert!(matches!(
+            router.handle(&kook, &click("vote:abc", "m")).await,
+            Err(KookError::Interaction(_))
+        ));
+        assert_eq!(*clicks.lock().unwrap(), vec!["vote 3 by user".to_string(), "page 2".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn skips_expired_messages_and_other_events() {
+        let (kook, _rx) = test_kook();
+        let clicks = Arc::new(Mutex::new(Vec::new()));
+        let router = router(clicks.clone());
+        router.expire_after("old", Duration::ZERO);
+        router.expire_after("new", Duration::from_secs(60));
+        assert_eq!(router.handle(&kook, &click("vote:1", "old")).await.unwrap(), RouteOutcome::Expired);
+        assert_eq!(router.handle(&kook, &click("vote:2", "new")).await.unwrap(), RouteOutcome::Handled);
+        let text: Event = serde_json::from_value(crate::api::testing::text_event_body("m")).unwrap();
+        assert_eq!(router.handle(&kook, &text).await.unwrap(), RouteOutcome::Ignored);
+        assert_eq!(*clicks.lock().unwrap(), vec!["vote 2 by user".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn huge_timeout_never_expires() {
+        let (kook, _rx) = test_kook();
+        let router = router(Arc::new(Mutex::new(Vec::new())));
+        router.expire_after("old", Duration::ZERO);
+        router.expire_after("old", Duration::MAX);
+        assert_eq!(router.handle(&kook, &click("vote:1", "old")).await.unwrap(), RouteOutcome::Handled);
+        // 最远的 deadline 加上保留时间会溢出, 清理时不能 panic
+        let mut far = Duration::MAX;
+        while Instant::now().checked_add(far).is_none() {
+            far /= 2;
+        }
+        let mut step = far;
+        while step > Duration::from_secs(1) {
+            step /= 2;
+            if Instant::now().checked_add(far + step).is_some() {
+                far += step;
+            }
+        }
+        assert!(Instant::now().checked_add(far + EXPIRED_RETENTION).is_none());
+        router.expire_after("far", far);
+        router.expire_after("new", Duration::from_secs(60));
+        assert_eq!(router.handle(&kook, &click("vote:2", "far")).await.unwrap(), RouteOutcome::Handled);
+    }
+}
@@ -1,11 +1,19 @@
 use std::{
     fmt::{write, Display},
-    sync::Arc, error::Error,
+    sync::Arc, error::Error, future::Future, time::Duration,
 };
 
 use serde::Deserialize;
+use serde_json::Value;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 
-use crate::{api::event::Event, error::{KookResult, KookError}};
+use crate::{
+    api::{event::Event, ratelimit::RateLimiter, retry::RetryPolicy, ws::{ReconnectPolicy, SequenceConfig}},
+    dispatch::{DispatchConfig, Dispatcher},
+    error::{KookResult, KookError},
+    middleware::{Filter, FilterLayer, Middleware, SkipSelf},
+    url::http_api,
+};
 
 pub struct BotInfo {
     pub id: String
@@ -15,33 +23,231 @@ pub struct Bot {
     pub token: Token,
     pub token_str: String,
     pub(crate) http_client: reqwest::Client,
+    pub(crate) rate_limiter: RateLimiter,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) base_url: String,
+    pub(crate) api_version: u32,
+}
+
+impl Bot {
+    pub fn builder(token: Token) -> BotBuilder {
+        BotBuilder::new(token)
+    }
+}
+
+pub struct BotBuilder {
+    token: Token,
+    http_client: Option<reqwest::Client>,
+    base_url: String,
+    api_version: u32,
+    rate_limit_retries: u32,
+    retry: RetryPolicy,
+}
+
+impl BotBuilder {
+    pub fn new(token: Token) -> Self {
+        Self {
+            token,
+            http_client: None,
+            base_url: http_api::DEFAULT_BASE_URL.to_string(),
+            api_version: http_api::DEFAULT_API_VERSION,
+            rate_limit_retries: RateLimiter::default().max_retries,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    // 接口地址, 例如代理或本地 mock 服务, 不含 /api/v3
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    // 自定义代理、TLS、超时、User-Agent 等
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    pub fn with_api_version(mut self, api_version: u32) -> Self {
+        self.api_version = api_version;
+        self
+    }
+
+    // 接口被限速 (429) 时的最大重试次数
+    pub fn with_rate_limit_retries(mut self, max_retries: u32) -> Self {
+        self.rate_limit_retries = max_retries;
+        self
+    }
+
+    // GET 和幂等 POST 遇到网络错误或 5xx 时的重试策略
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn build(self) -> Bot {
+        Bot {
+            token_str: self.token.to_string(),
+            token: self.token,
+            http_client: self.http_client.unwrap_or_default(),
+            rate_limiter: RateLimiter::new(self.rate_limit_retries),
+            retry: self.retry,
+            base_url: self.base_url,
+            api_version: self.api_version,
+        }
+    }
 }
 
 pub struct Kook<H: KookHandle + Clone + 'static> {
     pub bot: Bot,
     pub bot_info: BotInfo,
     pub(crate) handle: H,
+    pub(crate) sequence: SequenceConfig,
+    pub(crate) compress: bool,
+    pub(crate) reconnect: ReconnectPolicy,
+    pub(crate) shutdown_config: ShutdownConfig,
+    pub(crate) shutdown: CancellationToken,
+    pub(crate) abort: CancellationToken,
+    pub(crate) tasks: TaskTracker,
+    pub(crate) middleware: Vec<Arc<dyn Middleware>>,
+    pub(crate) dispatcher: Dispatcher,
 }
 
 impl<H: KookHandle + Send + Sync + Clone> Kook<H> {
     pub async fn new(token: Token, handle: H) -> KookResult<Self> {
-        let token_str = token.to_string();
-        let bot = Bot {
-            token,
-            token_str,
-            http_client: reqwest::Client::new(),
-        };
+        Self::from_bot(BotBuilder::new(token).build(), handle).await
+    }
+
+    // 使用 BotBuilder 构建的 Bot, 会请求 user/me 获取机器人信息
+    pub async fn from_bot(bot: Bot, handle: H) -> KookResult<Self> {
         let me = bot.user_me().await?;
-        Ok(Self {
+        Ok(Self::with_bot(bot, BotInfo { id: me.id }, handle))
+    }
+
+    pub(crate) fn with_bot(bot: Bot, bot_info: BotInfo, handle: H) -> Self {
+        Self {
             bot,
             handle,
-            bot_info: BotInfo { id: me.id }
-        })
+            bot_info,
+            sequence: SequenceConfig::default(),
+            compress: false,
+            reconnect: ReconnectPolicy::default(),
+            shutdown_config: ShutdownConfig::default(),
+            shutdown: CancellationToken::new(),
+            abort: CancellationToken::new(),
+            tasks: TaskTracker::new(),
+            middleware: Vec::new(),
+            dispatcher: Dispatcher::default(),
+        }
+        .with_skip_self()
+    }
+
+    fn with_skip_self(self) -> Self {
+        match self.handle.skip_self() {
+            true => self.with_filter(SkipSelf),
+            false => self,
+        }
+    }
+
+    // 按添加顺序执行, 先添加的在外层
+    pub fn with_middleware(mut self, middleware: impl Middleware) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    pub fn with_filter(self, filter: impl Filter) -> Self {
+        self.with_middleware(FilterLayer(filter))
+    }
+
+    pub fn with_sequence_config(mut self, config: SequenceConfig) -> Self {
+        self.sequence = config;
+        self
+    }
+
+    pub fn with_compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = policy.normalized();
+        self
+    }
+
+    pub fn with_shutdown_config(mut self, config: ShutdownConfig) -> Self {
+        self.shutdown_config = config;
+        self
+    }
+
+    pub fn with_dispatch_config(mut self, config: DispatchConfig) -> Self {
+        self.dispatcher = Dispatcher::new(config);
+        self
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            token: self.shutdown.clone(),
+        }
     }
 
     pub fn to_arc(self) -> Arc<Self> {
         Arc::new(self)
     }
+
+    pub(crate) async fn until_shutdown<F: Future>(&self, fut: F) -> Option<F::Output> {
+        tokio::select! {
+            biased;
+            _ = self.shutdown.cancelled() => None,
+            ret = fut => Some(ret),
+        }
+    }
+
+    // 等待正在执行的 on_event, 超过 grace_period 后取消剩余的任务
+    pub(crate) async fn finish(&self) {
+        self.tasks.close();
+        if tokio::time::timeout(self.shutdown_config.grace_period, self.tasks.wait()).await.is_err() {
+            tracing::warn!("cancel {} running handlers", self.tasks.len());
+            self.abort.cancel();
+            self.tasks.wait().await;
+        }
+        if self.shutdown_config.user_offline {
+            if let Err(err) = self.bot.user_offline().await {
+                tracing::error!("user offline failed: {}", err);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ShutdownConfig {
+    // 关闭后等待正在执行的 on_event 的时间
+    pub grace_period: Duration,
+    // 关闭后调用 user/offline
+    pub user_offline: bool,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            grace_period: Duration::from_secs(10),
+            user_offline: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    token: CancellationToken,
+}
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.token.is_cancelled()
+    }
 }
 
 pub trait KookHandle
@@ -56,6 +262,30 @@ where
     fn skip_self(&self) -> bool {
         true
     }
+    fn on_ready(&self, session_id: &str) {
+        tracing::info!("ready session_id:{}", session_id)
+    }
+    fn on_resume(&self, session_id: &str) {
+        tracing::info!("resumed session_id:{}", session_id)
+    }
+    // 收到 s=5 时在 on_disconnect 之后调用
+    fn on_reconnect(&self, code: i32, err: &str) {
+        tracing::warn!("reconnect code:{} err:{}", code, err)
+    }
+    fn on_disconnect(&self, reason: &DisconnectReason) {
+        tracing::warn!("disconnected:{:?}", reason)
+    }
+    fn on_unknown_event(&self, event: &Value) {
+        tracing::error!("unknown event:{}", event)
+    }
+}
+
+#[derive(Debug)]
+pub enum DisconnectReason {
+    PingTimeout,
+    Reconnect { code: i32, err: String },
+    Error(String),
+    Shutdown,
 }
 
 #[derive(Clone)]
@@ -64,6 +294,7 @@ pub struct EmptyKookHandle;
 impl KookHandle for EmptyKookHandle {
     type Err = KookError;
 
+    #[allow(clippy::manual_async_fn)]
     fn on_event(&self, _kook: Arc<Kook<Self>>, _event: Arc<Event>) -> impl std::future::Future<Output = Result<(), Self::Err>> + Send {
         async {
             Ok(())
@@ -85,3 +316,80 @@ impl Display for Token {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::testing::{test_bot, text_event_body};
+
+    #[derive(Clone)]
+    struct SlowHandle;
+
+    impl KookHandle for SlowHandle {
+        type Err = KookError;
+
+        async fn on_event(&self, _kook: Arc<Kook<Self>>, _event: Arc<Event>) -> Result<(), Self::Err> {
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+            Ok(())
+        }
+    }
+
+    fn event() -> Event {
+        serde_json::from_value(text_event_body("m1")).unwrap()
+    }
+
+    // 拿到 gate 的许可后才结束, 结束时记录 done
+    #[derive(Clone)]
+    struct GateHandle {
+        gate: Arc<tokio::sync::Semaphore>,
+        done: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    impl KookHandle for GateHandle {
+        type Err = KookError;
+
+        async fn on_event(&self, _kook: Arc<Kook<Self>>, _event: Arc<Event>) -> Result<(), Self::Err> {
+            self.gate.acquire().await.unwrap().forget();
+            self.done.store(true, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn finish_waits_for_running_handlers() {
+        let handle = GateHandle {
+            gate: Arc::new(tokio::sync::Semaphore::new(0)),
+            done: Arc::default(),
+        };
+        let kook = Kook::with_bot(test_bot(), BotInfo { id: "bot".to_string() }, handle.clone()).to_arc();
+        kook.dispatch(event());
+        kook.shutdown_handle().shutdown();
+        let finish = tokio::spawn({
+            let kook = kook.clone();
+            async move { kook.finish().await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!finish.is_finished());
+        assert!(!handle.done.load(std::sync::atomic::Ordering::SeqCst));
+
+        handle.gate.add_permits(1);
+        tokio::time::timeout(Duration::from_secs(5), finish).await.unwrap().unwrap();
+        assert!(handle.done.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn finish_cancels_handlers_after_grace_period() {
+        let kook = Kook::with_bot(test_bot(), BotInfo { id: "bot".to_string() }, SlowHandle)
+            .with_shutdown_config(ShutdownConfig {
+                grace_period: Duration::from_millis(50),
+                user_offline: false,
+            })
+            .to_arc();
+        kook.dispatch(event());
+        let handle = kook.shutdown_handle();
+        handle.shutdown();
+        assert!(handle.is_shutdown());
+        tokio::time::timeout(Duration::from_secs(5), kook.finish()).await.unwrap();
+        assert!(kook.tasks.is_empty());
+    }
+}
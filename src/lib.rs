@@ -1,13 +1,30 @@
 mod api;
+pub mod command;
+pub mod dispatch;
 mod error;
+pub mod interaction;
+pub mod router;
 mod kook;
+pub mod middleware;
 mod url;
 
+pub use api::{card, event, kmarkdown, objects, pagination, permission, request, response};
+
 pub use api::event::Event;
+pub use api::permission::Permissions;
+pub use api::retry::RetryPolicy;
+pub use api::webhook::WebhookConfig;
+pub use api::ws::ReconnectPolicy;
+pub use api::ws::SequenceConfig;
 pub use error::KookError;
 pub use kook::Bot;
+pub use kook::BotBuilder;
+pub use kook::DisconnectReason;
+pub use kook::EmptyKookHandle;
 pub use kook::Kook;
 pub use kook::KookHandle;
+pub use kook::ShutdownConfig;
+pub use kook::ShutdownHandle;
 pub use kook::Token;
 
 #[cfg(test)]
@@ -19,6 +36,10 @@ mod tests {
 
     #[tokio::test]
     async fn it_works() -> Result<(), Box<dyn std::error::Error>> {
+        // 需要真实的机器人 token 和网络, 没有设置 KOOK_BOT_TOKEN 时跳过
+        let Ok(token) = std::env::var("KOOK_BOT_TOKEN") else {
+            return Ok(());
+        };
         tracing_subscriber::registry()
             .with(tracing_subscriber::fmt::layer().with_filter(tracing_subscriber::filter::targets::Targets::new().with_default(LevelFilter::DEBUG)))
             .init();
@@ -27,6 +48,7 @@ mod tests {
 
         impl KookHandle for EchoHandle {
             type Err = KookError;
+            #[allow(clippy::manual_async_fn)]
             fn on_event(&self, kook: Arc<Kook<Self>>, event: Arc<Event>) -> impl std::future::Future<Output = Result<(), Self::Err>> + Send {
                 async move {
                     tracing::info!("enter echo");
@@ -39,7 +61,7 @@ mod tests {
             }
         }
 
-        let token = kook::Token::Bot("your bot token".to_string());
+        let token = kook::Token::Bot(token);
         let kook = Kook::new(token, EchoHandle).await?.to_arc();
         kook.event_loop().await?;
         Ok(())
@@ -0,0 +1,183 @@
+use std::{collections::HashSet, sync::Arc, time::Instant};
+
+use futures_util::{future::BoxFuture, FutureExt};
+
+use crate::{api::event::Event, Bot};
+
+#[derive(Clone, Copy)]
+pub struct Context<'a> {
+    pub bot: &'a Bot,
+    pub bot_id: &'a str,
+}
+
+// 包裹事件分发的中间件, 不调用 next 即拦截该事件
+pub trait Middleware: Send + Sync + 'static {
+    fn call<'a>(&'a self, ctx: Context<'a>, event: Arc<Event>, next: Next<'a>) -> BoxFuture<'a, ()>;
+}
+
+type Endpoint<'a> = dyn Fn(Arc<Event>) -> BoxFuture<'a, ()> + Send + Sync + 'a;
+
+pub struct Next<'a> {
+    ctx: Context<'a>,
+    chain: &'a [Arc<dyn Middleware>],
+    endpoint: &'a Endpoint<'a>,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(ctx: Context<'a>, chain: &'a [Arc<dyn Middleware>], endpoint: &'a Endpoint<'a>) -> Self {
+        Self { ctx, chain, endpoint }
+    }
+
+    pub async fn run(self, event: Arc<Event>) {
+        match self.chain.split_first() {
+            Some((middleware, chain)) => {
+                let next = Next {
+                    ctx: self.ctx,
+                    chain,
+                    endpoint: self.endpoint,
+                };
+                middleware.call(self.ctx, event, next).await
+            }
+            None => (self.endpoint)(event).await,
+        }
+    }
+}
+
+// 只决定是否放行的中间件
+pub trait Filter: Send + Sync + 'static {
+    fn allow(&self, ctx: Context<'_>, event: &Event) -> bool;
+}
+
+pub struct FilterLayer<F>(pub F);
+
+impl<F: Filter> Middleware for FilterLayer<F> {
+    fn call<'a>(&'a self, ctx: Context<'a>, event: Arc<Event>, next: Next<'a>) -> BoxFuture<'a, ()> {
+        async move {
+            if self.0.allow(ctx, &event) {
+                next.run(event).await
+            }
+        }
+        .boxed()
+    }
+}
+
+impl<F> Filter for F
+where
+    F: Fn(Context<'_>, &Event) -> bool + Send + Sync + 'static,
+{
+    fn allow(&self, ctx: Context<'_>, event: &Event) -> bool {
+        self(ctx, event)
+    }
+}
+
+// 忽略机器人自己发送的消息, KookHandle::skip_self 为 true 时默认启用
+pub struct SkipSelf;
+
+impl Filter for SkipSelf {
+    fn allow(&self, ctx: Context<'_>, event: &Event) -> bool {
+        event.author_id() != ctx.bot_id
+    }
+}
+
+pub struct IgnoreBots;
+
+impl Filter for IgnoreBots {
+    fn allow(&self, _ctx: Context<'_>, event: &Event) -> bool {
+        !event.author_is_bot()
+    }
+}
+
+pub struct IgnoreDirect;
+
+impl Filter for IgnoreDirect {
+    fn allow(&self, _ctx: Context<'_>, event: &Event) -> bool {
+        !event.is_direct()
+    }
+}
+
+// 只处理指定服务器的事件, 私聊事件不受影响
+pub struct OnlyGuilds(pub HashSet<String>);
+
+impl Filter for OnlyGuilds {
+    // is_none_or 需要 Rust 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    fn allow(&self, _ctx: Context<'_>, event: &Event) -> bool {
+        event.guild_id().map_or(true, |x| self.0.contains(x))
+    }
+}
+
+pub struct BlockUsers(pub HashSet<String>);
+
+impl Filter for BlockUsers {
+    fn allow(&self, _ctx: Context<'_>, event: &Event) -> bool {
+        !self.0.contains(event.author_id())
+    }
+}
+
+// 记录每个事件的处理耗时
+pub struct Timing;
+
+impl Middleware for Timing {
+    fn call<'a>(&'a self, _ctx: Context<'a>, event: Arc<Event>, next: Next<'a>) -> BoxFuture<'a, ()> {
+        async move {
+            let start = Instant::now();
+            let msg_id = event.msg_id().to_string();
+            next.run(event).await;
+            tracing::info!("handled event msg_id:{} in {:?}", msg_id, start.elapsed());
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::api::testing::{test_kook, text_event_body};
+
+    fn event(msg_id: &str, author_id: &str, guild_id: &str) -> Event {
+        let mut body = text_event_body(msg_id);
+        body["author_id"] = author_id.into();
+        body["extra"]["guild_id"] = guild_id.into();
+        serde_json::from_value(body).unwrap()
+    }
+
+    // 把 msg_id 改写为带前缀的新事件
+    struct Rewrite;
+
+    impl Middleware for Rewrite {
+        fn call<'a>(&'a self, _ctx: Context<'a>, event: Arc<Event>, next: Next<'a>) -> BoxFuture<'a, ()> {
+            async move {
+                let mut event = (*event).clone();
+                if let Event::Text(ref mut text) = event {
+                    text.msg_id = format!("rewritten-{}", text.msg_id);
+                }
+                next.run(Arc::new(event)).await
+            }
+            .boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn filters_and_rewrites_events() {
+        let (kook, mut rx) = test_kook();
+        let kook = Arc::try_unwrap(kook)
+            .ok()
+            .unwrap()
+            .with_filter(OnlyGuilds(HashSet::from(["guild".to_string()])))
+            .with_middleware(FilterLayer(|_ctx: Context<'_>, event: &Event| event.author_id() != "blocked"))
+            .with_middleware(Rewrite)
+            .with_middleware(Timing)
+            .to_arc();
+        kook.dispatch(event("self", "bot", "guild"));
+        kook.dispatch(event("other-guild", "user", "other"));
+        kook.dispatch(event("blocked", "blocked", "guild"));
+        kook.dispatch(event("ok", "user", "guild"));
+        let got = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.unwrap().unwrap();
+        assert_eq!(got, "rewritten-ok");
+        kook.tasks.close();
+        kook.tasks.wait().await;
+        assert!(rx.try_recv().is_err());
+    }
+}
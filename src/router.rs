@@ -0,0 +1,379 @@
+use std::{future::Future, sync::Arc};
+
+use futures_util::{future::BoxFuture, FutureExt};
+
+use crate::{
+    api::{
+        event::{CardEvent, Emoji, Event, FileEvent, ImageEvent, ItemEvent, KMarkdownEvent, SystemEvent, SystemExtra, TextEvent, VideoEvent},
+        objects::Channel,
+    },
+    error::{KookError, KookResult},
+    interaction::{ButtonClick, InteractionRouter, RouteOutcome},
+    kook::{Kook, KookHandle},
+    Bot,
+};
+
+// 处理函数的上下文, event 为原始事件
+#[derive(Clone)]
+pub struct EventContext {
+    pub kook: Arc<Kook<EventRouter>>,
+    pub event: Arc<Event>,
+}
+
+impl EventContext {
+    pub fn bot(&self) -> &Bot {
+        &self.kook.bot
+    }
+
+    // 频道消息为频道 id, 服务器系统事件为服务器 id
+    pub fn target_id(&self) -> &str {
+        self.event.target_id()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Reaction {
+    pub channel_id: String,
+    pub emoji: Emoji,
+    pub user_id: String,
+    pub msg_id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct MemberJoined {
+    pub user_id: String,
+    pub joined_at: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MemberExited {
+    pub user_id: String,
+    pub exited_at: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChannelDeleted {
+    pub id: String,
+    pub deleted_at: i64,
+}
+
+type Handler<T> = Option<Arc<dyn Fn(EventContext, T) -> BoxFuture<'static, KookResult<()>> + Send + Sync>>;
+
+fn handler<T, F, Fut>(f: F) -> Handler<T>
+where
+    F: Fn(EventContext, T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = KookResult<()>> + Send + 'static,
+{
+    Some(Arc::new(move |ctx, payload| f(ctx, payload).boxed()))
+}
+
+async fn call<T: Clone>(handler: &Handler<T>, ctx: EventContext, payload: &T) -> KookResult<()> {
+    match handler {
+        Some(handler) => handler(ctx, payload.clone()).await,
+        None => Ok(()),
+    }
+}
+
+// 按事件类型注册处理函数, 未注册的系统事件交给 on_system
+#[derive(Clone)]
+pub struct EventRouter {
+    skip_self: bool,
+    text: Handler<TextEvent>,
+    image: Handler<ImageEvent>,
+    video: Handler<VideoEvent>,
+    file: Handler<FileEvent>,
+    kmarkdown: Handler<KMarkdownEvent>,
+    card: Handler<CardEvent>,
+    item: Handler<ItemEvent>,
+    reaction_added: Handler<Reaction>,
+    reaction_deleted: Handler<Reaction>,
+    member_joined: Handler<MemberJoined>,
+    member_exited: Handler<MemberExited>,
+    channel_added: Handler<Channel>,
+    channel_updated: Handler<Channel>,
+    channel_deleted: Handler<ChannelDeleted>,
+    button_click: Handler<ButtonClick<()>>,
+    system: Handler<SystemEvent>,
+    interactions: Option<Arc<InteractionRouter<EventRouter>>>,
+}
+
+impl Default for EventRouter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+macro_rules! on_methods {
+    ($($name:ident => $field:ident: $t:ty),* $(,)?) => {
+        $(pub fn $name<F, Fut>(mut self, f: F) -> Self
+        where
+            F: Fn(EventContext, $t) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = KookResult<()>> + Send + 'static,
+        {
+            self.$field = handler(f);
+            self
+        })*
+    };
+}
+
+impl EventRouter {
+    pub fn new() -> Self {
+        Self {
+            skip_self: true,
+            text: None,
+            image: None,
+            video: None,
+            file: None,
+            kmarkdown: None,
+            card: None,
+            item: None,
+            reaction_added: None,
+            reaction_deleted: None,
+            member_joined: None,
+            member_exited: None,
+            channel_added: None,
+            channel_updated: None,
+            channel_deleted: None,
+            button_click: None,
+            system: None,
+            interactions: None,
+        }
+    }
+
+    pub fn with_skip_self(mut self, skip_self: bool) -> Self {
+        self.skip_self = skip_self;
+        self
+    }
+
+    // 按钮点击先交给 InteractionRouter, 匹配或已过期的点击不再交给 on_button_click
+    pub fn with_interactions(mut self, interactions: Arc<InteractionRouter<EventRouter>>) -> Self {
+        self.interactions = Some(interactions);
+        self
+    }
+
+    on_methods! {
+        on_text => text: TextEvent,
+        on_image => image: ImageEvent,
+        on_video => video: VideoEvent,
+        on_file => file: FileEvent,
+        on_kmarkdown => kmarkdown: KMarkdownEvent,
+        on_card => card: CardEvent,
+        on_item => item: ItemEvent,
+        on_reaction_added => reaction_added: Reaction,
+        on_reaction_deleted => reaction_deleted: Reaction,
+        on_member_joined => member_joined: MemberJoined,
+        on_member_exited => member_exited: MemberExited,
+        on_channel_added => channel_added: Channel,
+        on_channel_updated => channel_updated: Channel,
+        on_channel_deleted => channel_deleted: ChannelDeleted,
+        on_button_click => button_click: ButtonClick<()>,
+        on_system => system: SystemEvent,
+    }
+
+    async fn route_system(&self, ctx: EventContext, event: &SystemEvent) -> KookResult<()> {
+        match event.extra {
+            SystemExtra::AddedReaction { ref channel_id, ref emoji, ref user_id, ref msg_id } if self.reaction_added.is_some() => {
+                let reaction = Reaction {
+                    channel_id: channel_id.clone(),
+                    emoji: emoji.clone(),
+                    user_id: user_id.clone(),
+                    msg_id: msg_id.clone(),
+                };
+                call(&self.reaction_added, ctx, &reaction).await
+            }
+            SystemExtra::DeletedReaction { ref channel_id, ref emoji, ref user_id, ref msg_id } if self.reaction_deleted.is_some() => {
+                let reaction = Reaction {
+                    channel_id: channel_id.clone(),
+                    emoji: emoji.clone(),
+                    user_id: user_id.clone(),
+                    msg_id: msg_id.clone(),
+                };
+                call(&self.reaction_deleted, ctx, &reaction).await
+            }
+            SystemExtra::JoinedGuild { ref user_id, joined_at } if self.member_joined.is_some() => {
+                let member = MemberJoined {
+                    user_id: user_id.clone(),
+                    joined_at,
+                };
+                call(&self.member_joined, ctx, &member).await
+            }
+            SystemExtra::ExitedGuild { ref user_id, exited_at } if self.member_exited.is_some() => {
+                let member = MemberExited {
+                    user_id: user_id.clone(),
+                    exited_at,
+                };
+                call(&self.member_exited, ctx, &member).await
+            }
+            SystemExtra::AddedChannel(ref channel) if self.channel_added.is_some() => call(&self.channel_added, ctx, channel).await,
+            SystemExtra::UpdatedChannel(ref channel) if self.channel_updated.is_some() => call(&self.channel_updated, ctx, channel).await,
+            SystemExtra::DeletedChannel { ref id, deleted_at } if self.channel_deleted.is_some() => {
+                let channel = ChannelDeleted { id: id.clone(), deleted_at };
+                call(&self.channel_deleted, ctx, &channel).await
+            }
+            SystemExtra::MessageBtnClick { .. } if self.interactions.is_some() || self.button_click.is_some() => {
+                self.route_click(ctx, event).await
+            }
+            _ => call(&self.system, ctx, event).await,
+        }
+    }
+
+    async fn route_click(&self, ctx: EventContext, event: &SystemEvent) -> KookResult<()> {
+        if let Some(ref interactions) = self.interactions {
+            match interactions.handle(&ctx.kook, &ctx.event).await? {
+                RouteOutcome::Handled | RouteOutcome::Expired => return Ok(()),
+                RouteOutcome::Unmatched | RouteOutcome::Ignored => {}
+            }
+        }
+        match ButtonClick::from_event(&ctx.event) {
+            Some(click) if self.button_click.is_some() => call(&self.button_click, ctx, &click).await,
+            _ => call(&self.system, ctx, event).await,
+        }
+    }
+}
+
+impl KookHandle for EventRouter {
+    type Err = KookError;
+
+    async fn on_event(&self, kook: Arc<Kook<Self>>, event: Arc<Event>) -> Result<(), Self::Err> {
+        let ctx = EventContext { kook, event: event.clone() };
+        match *event {
+            Event::Text(ref e) => call(&self.text, ctx, e).await,
+            Event::Image(ref e) => call(&self.image, ctx, e).await,
+            Event::Video(ref e) => call(&self.video, ctx, e).await,
+            Event::File(ref e) => call(&self.file, ctx, e).await,
+            Event::KMarkdown(ref e) => call(&self.kmarkdown, ctx, e).await,
+            Event::Card(ref e) => call(&self.card, ctx, e).await,
+            Event::Item(ref e) => call(&self.item, ctx, e).await,
+            Event::System(ref e) => self.route_system(ctx, e).await,
+        }
+    }
+
+    fn skip_self(&self) -> bool {
+        self.skip_self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        api::testing::{test_bot, text_event_body},
+        kook::BotInfo,
+    };
+
+    fn system(extra_type: &str, body: serde_json::Value) -> Arc<Event> {
+        let event = serde_json::json!({
+            "channel_type": "GROUP",
+            "type": 255,
+            "target_id": "guild",
+            "author_id": "1",
+            "content": "[系统消息]",
+            "extra": {"type": extra_type, "body": body},
+            "msg_id": "system",
+            "msg_timestamp": 0,
+            "nonce": ""
+        });
+        Arc::new(serde_json::from_value(event).unwrap())
+    }
+
+    async fn run(router: EventRouter, event: Arc<Event>) {
+        let kook = Kook::with_bot(test_bot(), BotInfo { id: "bot".to_string() }, router.clone()).to_arc();
+        router.on_event(kook, event).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn routes_by_kind() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let (text, joined, click, other) = (calls.clone(), calls.clone(), calls.clone(), calls.clone());
+        let router = EventRouter::new()
+            .on_text(move |ctx, event| {
+                let text = text.clone();
+                async move {
+                    text.lock().unwrap().push(format!("text {} in {}", event.content, ctx.target_id()));
+                    Ok(())
+                }
+            })
+            .on_member_joined(move |ctx, member| {
+                let joined = joined.clone();
+                async move {
+                    joined.lock().unwrap().push(format!("joined {} {}", member.user_id, ctx.target_id()));
+                    Ok(())
+                }
+            })
+            .on_button_click(move |_ctx, button| {
+                let click = click.clone();
+                async move {
+                    click.lock().unwrap().push(format!("click {}", button.value));
+                    Ok(())
+                }
+            })
+            .on_system(move |_ctx, event| {
+                let other = other.clone();
+                async move {
+                    other.lock().unwrap().push(format!("system {}", event.msg_id));
+                    Ok(())
+                }
+            });
+
+        run(router.clone(), Arc::new(serde_json::from_value(text_event_body("m")).unwrap())).await;
+        run(router.clone(), system("joined_guild", serde_json::json!({"user_id": "u", "joined_at": 1}))).await;
+        run(
+            router.clone(),
+            system(
+                "message_btn_click",
+                serde_json::json!({"value": "v", "msg_id": "m", "user_id": "u", "target_id": "c"}),
+            ),
+        )
+        .await;
+        run(router.clone(), system("exited_guild", serde_json::json!({"user_id": "u", "exited_at": 1}))).await;
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "text hello in channel".to_string(),
+                "joined u guild".to_string(),
+                "click v".to_string(),
+                "system system".to_string()
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn ignores_unregistered_kinds() {
+        run(EventRouter::new(), Arc::new(serde_json::from_value(text_event_body("m")).unwrap())).await;
+        run(EventRouter::new(), system("joined_guild", serde_json::json!({"user_id": "u", "joined_at": 1}))).await;
+    }
+
+    #[tokio::test]
+    async fn routes_button_clicks_through_interactions() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let (vote, click) = (calls.clone(), calls.clone());
+        let interactions = Arc::new(InteractionRouter::new().on("vote:", move |_kook, button: ButtonClick<u32>| {
+            let vote = vote.clone();
+            async move {
+                vote.lock().unwrap().push(format!("vote {}", button.state));
+                Ok(())
+            }
+        }));
+        interactions.expire_after("old", std::time::Duration::ZERO);
+        let router = EventRouter::new().with_interactions(interactions).on_button_click(move |_ctx, button| {
+            let click = click.clone();
+            async move {
+                click.lock().unwrap().push(format!("click {}", button.value));
+                Ok(())
+            }
+        });
+        let button = |value: &str, msg_id: &str| {
+            system(
+                "message_btn_click",
+                serde_json::json!({"value": value, "msg_id": msg_id, "user_id": "u", "target_id": "c"}),
+            )
+        };
+
+        run(router.clone(), button("vote:3", "m")).await;
+        run(router.clone(), button("vote:4", "old")).await;
+        run(router.clone(), button("other", "m")).await;
+        assert_eq!(*calls.lock().unwrap(), vec!["vote 3".to_string(), "click other".to_string()]);
+    }
+}
@@ -1,21 +1,63 @@
 pub(crate) mod http_api {
-    macro_rules! concat_url {
-        ($e:expr) => {
-            concat!("https://www.kookapp.cn", $e)
-        };
-    }
-
-    pub static GUILD_LIST: &'static str = concat_url!("/api/v3/guild/list");
-    pub static GUILD_VIEW: &'static str = concat_url!("/api/v3/guild/view");
-    pub static GUILD_USER_LIST: &'static str = concat_url!("/api/v3/guild/user-list");
-    pub static GUILD_NICKNAME: &'static str = concat_url!("/api/v3/guild/nickname");
-    pub static GUILD_LEAVE: &'static str = concat_url!("/api/v3/guild/leave");
-
-    pub static GATEWAY_INDEX: &'static str = concat_url!("/api/v3/gateway/index");
-    
-    pub static USER_ME: &'static str = concat_url!("/api/v3/user/me");
-    pub static USER_VIEW: &'static str = concat_url!("/api/v3/user/view");
-    pub static USER_OFFLINE: &'static str = concat_url!("/api/v3/user/offline");
-
-    pub static MESSAGE_CREATE: &'static str = concat_url!("/api/v3/message/create");
+    pub static DEFAULT_BASE_URL: &str = "https://www.kookapp.cn";
+    pub static DEFAULT_API_VERSION: u32 = 3;
+
+    // 以下为相对于 {base_url}/api/v{api_version}/ 的接口路径
+
+    pub static GUILD_LIST: &str = "guild/list";
+    pub static GUILD_VIEW: &str = "guild/view";
+    pub static GUILD_USER_LIST: &str = "guild/user-list";
+    pub static GUILD_NICKNAME: &str = "guild/nickname";
+    pub static GUILD_LEAVE: &str = "guild/leave";
+
+    pub static GUILD_ROLE_LIST: &str = "guild-role/list";
+    pub static GUILD_ROLE_CREATE: &str = "guild-role/create";
+    pub static GUILD_ROLE_UPDATE: &str = "guild-role/update";
+    pub static GUILD_ROLE_DELETE: &str = "guild-role/delete";
+    pub static GUILD_ROLE_GRANT: &str = "guild-role/grant";
+    pub static GUILD_ROLE_REVOKE: &str = "guild-role/revoke";
+
+    pub static CHANNEL_LIST: &str = "channel/list";
+    pub static CHANNEL_VIEW: &str = "channel/view";
+    pub static CHANNEL_CREATE: &str = "channel/create";
+    pub static CHANNEL_UPDATE: &str = "channel/update";
+    pub static CHANNEL_DELETE: &str = "channel/delete";
+    pub static CHANNEL_USER_LIST: &str = "channel/user-list";
+    pub static CHANNEL_MOVE_USER: &str = "channel/move-user";
+
+    pub static CHANNEL_ROLE_INDEX: &str = "channel-role/index";
+    pub static CHANNEL_ROLE_CREATE: &str = "channel-role/create";
+    pub static CHANNEL_ROLE_UPDATE: &str = "channel-role/update";
+    pub static CHANNEL_ROLE_DELETE: &str = "channel-role/delete";
+
+    pub static ASSET_CREATE: &str = "asset/create";
+
+    pub static GATEWAY_INDEX: &str = "gateway/index";
+
+    pub static USER_ME: &str = "user/me";
+    pub static USER_VIEW: &str = "user/view";
+    pub static USER_OFFLINE: &str = "user/offline";
+
+    pub static MESSAGE_LIST: &str = "message/list";
+    pub static MESSAGE_VIEW: &str = "message/view";
+    pub static MESSAGE_CREATE: &str = "message/create";
+    pub static MESSAGE_UPDATE: &str = "message/update";
+    pub static MESSAGE_DELETE: &str = "message/delete";
+    pub static MESSAGE_REACTION_LIST: &str = "message/reaction-list";
+    pub static MESSAGE_ADD_REACTION: &str = "message/add-reaction";
+    pub static MESSAGE_DELETE_REACTION: &str = "message/delete-reaction";
+
+    pub static USER_CHAT_LIST: &str = "user-chat/list";
+    pub static USER_CHAT_VIEW: &str = "user-chat/view";
+    pub static USER_CHAT_CREATE: &str = "user-chat/create";
+    pub static USER_CHAT_DELETE: &str = "user-chat/delete";
+
+    pub static DIRECT_MESSAGE_LIST: &str = "direct-message/list";
+    pub static DIRECT_MESSAGE_VIEW: &str = "direct-message/view";
+    pub static DIRECT_MESSAGE_CREATE: &str = "direct-message/create";
+    pub static DIRECT_MESSAGE_UPDATE: &str = "direct-message/update";
+    pub static DIRECT_MESSAGE_DELETE: &str = "direct-message/delete";
+    pub static DIRECT_MESSAGE_REACTION_LIST: &str = "direct-message/reaction-list";
+    pub static DIRECT_MESSAGE_ADD_REACTION: &str = "direct-message/add-reaction";
+    pub static DIRECT_MESSAGE_DELETE_REACTION: &str = "direct-message/delete-reaction";
 }
\ No newline at end of file